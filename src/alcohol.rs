@@ -0,0 +1,137 @@
+use crate::units::GRAMS_ETHANOL_PER_STANDARD_DRINK;
+
+/// Widmark's elimination rate, in g/100mL (BAC percentage points) per hour.
+pub const ELIMINATION_PER_HOUR: f64 = 0.015;
+
+/// The step at which drinking episodes are simulated.
+const STEP_MINUTES: f64 = 1.0;
+
+//////////////////////////////////////////////// Sex ///////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sex {
+    Male,
+    Female,
+}
+
+impl Sex {
+    /// Widmark's distribution ratio: the fraction of body mass alcohol distributes into.
+    pub fn widmark_r(self) -> f64 {
+        match self {
+            Sex::Male => 0.68,
+            Sex::Female => 0.55,
+        }
+    }
+}
+
+////////////////////////////////////////////// Profile /////////////////////////////////////////////
+
+/// What the Widmark formula needs to know about the drinker.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Profile {
+    pub body_weight_kg: f64,
+    pub sex: Sex,
+}
+
+impl Profile {
+    pub fn load(path: &str) -> Result<Self, std::io::Error> {
+        let profile = std::fs::read_to_string(path)?;
+        serde_json::from_str(&profile)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    /// Load the profile at `NOTAPSYCH_PROFILE`.  No profile means no BAC estimates, so a missing
+    /// variable is not an error; a profile that cannot be read is.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("NOTAPSYCH_PROFILE").ok()?;
+        match Self::load(&path) {
+            Ok(profile) => Some(profile),
+            Err(err) => {
+                eprintln!("could not load profile from {path}: {err}");
+                std::process::exit(13);
+            }
+        }
+    }
+}
+
+/// Estimate peak blood alcohol concentration (g/100mL) for `standard_drinks` consumed at an even
+/// pace over `duration_hours`, using the Widmark formula with elimination over time.
+///
+/// This is an estimate for spotting trends, not a measurement:  it assumes instant absorption of
+/// each sip and ignores food, medication and individual metabolism.
+pub fn widmark_peak_bac(standard_drinks: f64, duration_hours: f64, profile: &Profile) -> f64 {
+    if standard_drinks <= 0.0 || profile.body_weight_kg <= 0.0 {
+        return 0.0;
+    }
+    let grams = standard_drinks * GRAMS_ETHANOL_PER_STANDARD_DRINK;
+    // Widmark:  BAC = A / (r * W), scaled from grams per gram of body mass to g/100mL.
+    let bac_per_gram = 100.0 / (profile.sex.widmark_r() * profile.body_weight_kg * 1000.0);
+    let step_hours = STEP_MINUTES / 60.0;
+    let steps = (duration_hours.max(0.0) / step_hours).ceil().max(1.0) as usize;
+    let grams_per_step = grams / steps as f64;
+    let mut bac = 0.0f64;
+    let mut peak = 0.0f64;
+    for _ in 0..steps {
+        bac += grams_per_step * bac_per_gram;
+        peak = peak.max(bac);
+        bac = (bac - ELIMINATION_PER_HOUR * step_hours).max(0.0);
+    }
+    peak
+}
+
+/// Hours until an estimated BAC is fully eliminated.
+pub fn hours_to_sober(bac: f64) -> f64 {
+    bac.max(0.0) / ELIMINATION_PER_HOUR
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILE: Profile = Profile {
+        body_weight_kg: 80.0,
+        sex: Sex::Male,
+    };
+
+    #[test]
+    fn widmark_peak_bac_all_at_once() {
+        // 14g / (0.68 * 80,000g) * 100
+        let bac = widmark_peak_bac(1.0, 0.0, &PROFILE);
+        assert!((bac - 0.025735).abs() < 1e-6, "{bac}");
+    }
+
+    #[test]
+    fn widmark_peak_bac_female_is_higher() {
+        let female = Profile {
+            body_weight_kg: 80.0,
+            sex: Sex::Female,
+        };
+        assert!(widmark_peak_bac(3.0, 0.0, &female) > widmark_peak_bac(3.0, 0.0, &PROFILE));
+    }
+
+    #[test]
+    fn widmark_peak_bac_spread_out_is_lower() {
+        let fast = widmark_peak_bac(4.0, 0.0, &PROFILE);
+        let slow = widmark_peak_bac(4.0, 4.0, &PROFILE);
+        assert!(slow < fast, "{slow} >= {fast}");
+        // Four hours of elimination at most takes 0.06 off.
+        assert!(slow > fast - 4.0 * ELIMINATION_PER_HOUR, "{slow}");
+    }
+
+    #[test]
+    fn widmark_peak_bac_nothing() {
+        assert_eq!(0.0, widmark_peak_bac(0.0, 2.0, &PROFILE));
+        let weightless = Profile {
+            body_weight_kg: 0.0,
+            sex: Sex::Male,
+        };
+        assert_eq!(0.0, widmark_peak_bac(2.0, 2.0, &weightless));
+    }
+
+    #[test]
+    fn hours_to_sober_at_elimination_rate() {
+        assert!((hours_to_sober(0.03) - 2.0).abs() < 1e-9);
+        assert_eq!(0.0, hours_to_sober(-0.01));
+    }
+}
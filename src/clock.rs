@@ -0,0 +1,413 @@
+use chrono::{DateTime, FixedOffset, NaiveTime};
+
+/// Words that carry no meaning in an answer about when or how long, e.g. "I woke up at about".
+const FILLER: &[&str] = &[
+    "i",
+    "woke",
+    "wake",
+    "waking",
+    "up",
+    "got",
+    "get",
+    "since",
+    "at",
+    "around",
+    "about",
+    "approx",
+    "approximately",
+    "roughly",
+    "maybe",
+    "like",
+    "ish",
+    "just",
+    "only",
+    "~",
+    "slept",
+    "sleep",
+    "for",
+    "of",
+    "total",
+    "in",
+];
+
+/// Units of duration, in hours.
+const HOUR: f64 = 1.0;
+const MINUTE: f64 = 1.0 / 60.0;
+
+const NUMBERS: &[(&str, f64)] = &[
+    ("zero", 0.0),
+    ("one", 1.0),
+    ("two", 2.0),
+    ("three", 3.0),
+    ("four", 4.0),
+    ("five", 5.0),
+    ("six", 6.0),
+    ("seven", 7.0),
+    ("eight", 8.0),
+    ("nine", 9.0),
+    ("ten", 10.0),
+    ("eleven", 11.0),
+    ("twelve", 12.0),
+    ("thirteen", 13.0),
+    ("fourteen", 14.0),
+    ("fifteen", 15.0),
+    ("sixteen", 16.0),
+    ("twenty", 20.0),
+    ("thirty", 30.0),
+    ("forty", 40.0),
+    ("forty-five", 45.0),
+    ("fifty", 50.0),
+    ("sixty", 60.0),
+    ("ninety", 90.0),
+];
+
+/////////////////////////////////////////////// Parser /////////////////////////////////////////////
+
+/// A deterministic parser to try on an answer before asking the model, and the answer field it
+/// fills.  In a questionnaire file this is `parse = { hours-since = "awake_hours" }` or
+/// `parse = { duration = "sleep_hours" }`.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Parser {
+    /// Hours from a time of day, e.g. "7:30am" or "yesterday at 11pm", until now.
+    HoursSince(String),
+    /// Hours in a duration, e.g. "8h" or "6 and a half hours".
+    Duration(String),
+}
+
+impl Parser {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Parser::HoursSince(_) => "hours-since",
+            Parser::Duration(_) => "duration",
+        }
+    }
+
+    /// The answer field this parser fills.
+    pub fn field(&self) -> &str {
+        match self {
+            Parser::HoursSince(field) | Parser::Duration(field) => field,
+        }
+    }
+
+    /// Parse `answer` into hours and a justification, or None if it is not an expression this
+    /// parser understands.
+    pub fn parse(&self, answer: &str, now: DateTime<FixedOffset>) -> Option<(f64, String)> {
+        match self {
+            Parser::HoursSince(_) => {
+                let then = time_of_day(answer, now)?;
+                let hours = (now - then).num_minutes() as f64 / 60.0;
+                Some((
+                    hours,
+                    format!(
+                        "{:?} is {}, {hours} hours before {}.",
+                        answer.trim(),
+                        then.format("%a %H:%M"),
+                        now.format("%a %H:%M")
+                    ),
+                ))
+            }
+            Parser::Duration(_) => {
+                let hours = duration_hours(answer)?;
+                Some((hours, format!("{:?} is {hours} hours.", answer.trim())))
+            }
+        }
+    }
+}
+
+///////////////////////////////////////////// Tokenizing ////////////////////////////////////////////
+
+/// Lowercase `answer` and split it into words, numbers and units, so "7h30" and "7 h 30" and
+/// "7:30AM," and "7:30 am" read the same.
+fn tokens(answer: &str) -> Vec<String> {
+    let answer = answer
+        .to_lowercase()
+        .replace("a.m.", "am")
+        .replace("p.m.", "pm")
+        .replace("-ish", " ish");
+    let mut tokens = vec![];
+    for word in answer.split(|c: char| c.is_whitespace() || ",;!?".contains(c)) {
+        let word = word.trim_end_matches('.');
+        let mut current = String::new();
+        let mut numeric = false;
+        for c in word.chars() {
+            let is_numeric = c.is_ascii_digit() || c == '.' || c == ':';
+            if !current.is_empty() && is_numeric != numeric {
+                tokens.push(std::mem::take(&mut current));
+            }
+            numeric = is_numeric;
+            current.push(c);
+        }
+        if !current.is_empty() {
+            tokens.push(current);
+        }
+    }
+    tokens
+}
+
+fn number(token: &str) -> Option<f64> {
+    if let Ok(number) = token.parse::<f64>() {
+        return Some(number);
+    }
+    NUMBERS
+        .iter()
+        .find(|(word, _)| *word == token)
+        .map(|(_, number)| *number)
+}
+
+///////////////////////////////////////////// Time of day ///////////////////////////////////////////
+
+/// The most recent moment at or before `now` that `answer` names, e.g. "7:30am", "since noon",
+/// "yesterday at 11pm", or "this morning at 6".  None when the answer is not understood, or when
+/// an hour without am or pm could be either.
+pub fn time_of_day(answer: &str, now: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    let today = now.date_naive();
+    let yesterday = today.pred_opt()?;
+    let mut days = vec![today, yesterday];
+    let mut meridiem: Option<bool> = None;
+    let mut overnight = false;
+    let mut time: Option<(u32, u32)> = None;
+    let tokens = tokens(answer);
+    let mut idx = 0;
+    while idx < tokens.len() {
+        let token = tokens[idx].as_str();
+        let next = tokens.get(idx + 1).map(String::as_str);
+        match (token, next) {
+            ("today", _) => days = vec![today],
+            ("yesterday", _) => days = vec![yesterday],
+            ("this", Some("morning")) => {
+                days = vec![today];
+                meridiem = Some(false);
+                idx += 1;
+            }
+            ("this", Some("afternoon" | "evening")) => {
+                days = vec![today];
+                meridiem = Some(true);
+                idx += 1;
+            }
+            ("tonight", _) => {
+                days = vec![today];
+                meridiem = Some(true);
+            }
+            ("last", Some("night")) => {
+                overnight = true;
+                idx += 1;
+            }
+            ("am" | "pm", _) if time.is_some() => meridiem = Some(token == "pm"),
+            ("noon", _) if time.is_none() => time = Some((12, 0)),
+            ("midnight", _) if time.is_none() => time = Some((0, 0)),
+            ("o'clock" | "oclock", _) if time.is_some() => {}
+            (token, _) if time.is_none() && token.starts_with(|c: char| c.is_ascii_digit()) => {
+                let (hour, minute) = token.split_once(':').unwrap_or((token, "0"));
+                let (hour, minute) = (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?);
+                if hour > 23 || minute > 59 {
+                    return None;
+                }
+                time = Some((hour, minute));
+            }
+            (token, _) if FILLER.contains(&token) => {}
+            _ => return None,
+        }
+        idx += 1;
+    }
+    let (hour, minute) = time?;
+    let hours = match (meridiem, hour) {
+        (_, 0) | (_, 13..) => vec![hour],
+        (Some(false), 12) => vec![0],
+        (Some(false), _) => vec![hour],
+        (Some(true), 12) => vec![12],
+        (Some(true), _) => vec![hour + 12],
+        (None, _) if tokens.iter().any(|t| t == "noon") => vec![12],
+        (None, 12) => vec![0, 12],
+        (None, _) => vec![hour, hour + 12],
+    };
+    // "Last night" is from yesterday evening through the small hours of this morning.
+    let night = (
+        yesterday.and_hms_opt(18, 0, 0)?,
+        today.and_hms_opt(6, 0, 0)?,
+    );
+    let readings = hours
+        .iter()
+        .filter_map(|hour| {
+            days.iter()
+                .filter_map(|day| Some(day.and_time(NaiveTime::from_hms_opt(*hour, minute, 0)?)))
+                .filter(|then| !overnight || (night.0 <= *then && *then <= night.1))
+                .filter_map(|then| then.and_local_timezone(now.timezone()).single())
+                .filter(|then| *then <= now)
+                .max()
+        })
+        .collect::<Vec<_>>();
+    // A bare "3" could be 3am or 3pm.  When both readings fit, leave it to the model rather than
+    // guess; "woke up at 3" is not obviously an hour ago.
+    match readings.as_slice() {
+        [then] => Some(*then),
+        _ => None,
+    }
+}
+
+////////////////////////////////////////////// Duration ////////////////////////////////////////////
+
+/// The hours in a duration such as "8h", "7.5 hours", "7h30", "6 and a half hours", "an hour
+/// and a half", "90 minutes", or a bare "8".
+pub fn duration_hours(answer: &str) -> Option<f64> {
+    let tokens = tokens(answer);
+    let mut total: Option<f64> = None;
+    let mut pending: Option<f64> = None;
+    let mut last_unit: Option<f64> = None;
+    for (idx, token) in tokens.iter().enumerate() {
+        let next = tokens.get(idx + 1).map(String::as_str);
+        let unit = match token.as_str() {
+            "h" | "hr" | "hrs" | "hour" | "hours" => Some(HOUR),
+            "m" | "min" | "mins" | "minute" | "minutes" => Some(MINUTE),
+            _ => None,
+        };
+        if let Some(unit) = unit {
+            *total.get_or_insert(0.0) += pending.take()? * unit;
+            last_unit = Some(unit);
+            continue;
+        }
+        match token.as_str() {
+            "a" | "an" if pending.is_none() && next != Some("half") => pending = Some(1.0),
+            "a" | "an" | "and" => {}
+            "half" => match (pending, last_unit) {
+                (Some(n), _) => pending = Some(n + 0.5),
+                (None, Some(unit)) if next.is_none() || next == Some("ish") => {
+                    *total.get_or_insert(0.0) += 0.5 * unit
+                }
+                (None, _) => pending = Some(0.5),
+            },
+            token if token.contains(':') => {
+                let (hours, minutes) = token.split_once(':')?;
+                let (hours, minutes) = (hours.parse::<f64>().ok()?, minutes.parse::<f64>().ok()?);
+                if pending.is_some() || minutes >= 60.0 {
+                    return None;
+                }
+                *total.get_or_insert(0.0) += hours + minutes / 60.0;
+                last_unit = Some(HOUR);
+            }
+            token if FILLER.contains(&token) => {}
+            token => {
+                if pending.is_some() {
+                    return None;
+                }
+                pending = Some(number(token)?);
+            }
+        }
+    }
+    // A number after the hours is minutes ("7h30"); a number alone is hours ("8").
+    match (pending, last_unit, total) {
+        (Some(n), Some(HOUR), Some(total)) => Some(total + n / 60.0),
+        (Some(n), None, None) => Some(n),
+        (None, _, total) => total,
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339("2025-03-08T15:15:00-08:00").unwrap()
+    }
+
+    fn at(when: &str) -> Option<DateTime<FixedOffset>> {
+        Some(DateTime::parse_from_rfc3339(when).unwrap())
+    }
+
+    #[test]
+    fn time_of_day_most_recent() {
+        assert_eq!(
+            at("2025-03-08T07:30:00-08:00"),
+            time_of_day("7:30am", now())
+        );
+        assert_eq!(
+            at("2025-03-08T07:30:00-08:00"),
+            time_of_day("7:30 A.M.", now())
+        );
+        assert_eq!(
+            at("2025-03-08T03:00:00-08:00"),
+            time_of_day("woke up at 3am", now())
+        );
+        assert_eq!(
+            at("2025-03-08T12:00:00-08:00"),
+            time_of_day("since noon", now())
+        );
+        assert_eq!(at("2025-03-07T16:00:00-08:00"), time_of_day("4pm", now()));
+    }
+
+    #[test]
+    fn time_of_day_with_day_words() {
+        assert_eq!(
+            at("2025-03-07T05:30:00-08:00"),
+            time_of_day("5:30am yesterday", now())
+        );
+        assert_eq!(
+            at("2025-03-07T23:00:00-08:00"),
+            time_of_day("yesterday at 11pm", now())
+        );
+        assert_eq!(
+            at("2025-03-08T06:00:00-08:00"),
+            time_of_day("this morning at 6", now())
+        );
+        assert_eq!(
+            at("2025-03-08T02:00:00-08:00"),
+            time_of_day("2 last night", now())
+        );
+    }
+
+    #[test]
+    fn time_of_day_declines() {
+        assert_eq!(None, time_of_day("after the meeting", now()));
+        assert_eq!(None, time_of_day("25:00", now()));
+        assert_eq!(None, time_of_day("tonight at 9", now()));
+    }
+
+    #[test]
+    fn time_of_day_leaves_bare_hours_to_the_model() {
+        assert_eq!(None, time_of_day("woke up at 3", now()));
+        assert_eq!(None, time_of_day("7:30", now()));
+        assert_eq!(None, time_of_day("at 12", now()));
+        assert_eq!(
+            None,
+            Parser::HoursSince("awake_hours".to_string()).parse("woke up at 3", now())
+        );
+        // A 24-hour time, or one the day words pin down, has only one reading.
+        assert_eq!(at("2025-03-08T13:00:00-08:00"), time_of_day("13:00", now()));
+        assert_eq!(
+            at("2025-03-08T02:00:00-08:00"),
+            time_of_day("2 last night", now())
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(Some(8.0), duration_hours("8"));
+        assert_eq!(Some(8.0), duration_hours("8h"));
+        assert_eq!(Some(7.5), duration_hours("7.5 hours"));
+        assert_eq!(Some(7.5), duration_hours("7h30"));
+        assert_eq!(Some(7.5), duration_hours("7:30"));
+        assert_eq!(Some(6.5), duration_hours("6 and a half hours"));
+        assert_eq!(Some(1.5), duration_hours("an hour and a half"));
+        assert_eq!(Some(1.5), duration_hours("90 minutes"));
+        assert_eq!(Some(7.0), duration_hours("about seven hours"));
+    }
+
+    #[test]
+    fn durations_decline() {
+        assert_eq!(None, duration_hours("crashed around 2, up at 7ish"));
+        assert_eq!(None, duration_hours("not enough"));
+        assert_eq!(None, duration_hours(""));
+    }
+
+    #[test]
+    fn parser_fills_its_field() {
+        let parser = Parser::Duration("sleep_hours".to_string());
+        assert_eq!("sleep_hours", parser.field());
+        let (hours, justification) = parser.parse("7h30", now()).unwrap();
+        assert_eq!(7.5, hours);
+        assert!(justification.contains("7.5"), "{justification}");
+        let parser = Parser::HoursSince("awake_hours".to_string());
+        assert_eq!(Some(7.75), parser.parse("7:30am", now()).map(|(h, _)| h));
+    }
+}
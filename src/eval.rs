@@ -0,0 +1,537 @@
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use arrrg::CommandLine;
+use chrono::{DateTime, FixedOffset, Local};
+
+use crate::questionnaire::{Question, Questionnaire};
+
+pub mod synthetic;
+
+const USAGE: &str = "USAGE: notapsych-eval [--questions A.toml,B.toml] [--models M,N] [--recordings FILE [--offline]] [--baseline FILE] [--save FILE] CORPUS...";
+
+/// Numbers within this of each other are equal even when a case allows no tolerance.
+const EPSILON: f64 = 1e-9;
+
+//////////////////////////////////////////////// Case //////////////////////////////////////////////
+
+/// One labeled answer:  what the user said to a question and what should be extracted from it.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Case {
+    /// The slug of the question answered.
+    pub question: String,
+    pub answer: String,
+    /// When the answer was given (RFC 3339), for questions whose prompt includes the time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub now: Option<String>,
+    /// The fields to check and their expected values.  Fields left out, like the justification,
+    /// are not checked.
+    pub expected: serde_json::Map<String, serde_json::Value>,
+    /// How far each numeric field may be from its expected value; absent fields must be exact.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tolerance: BTreeMap<String, f64>,
+}
+
+impl Case {
+    /// The time to put in the prompt for this case.
+    pub fn now(&self) -> Result<Option<DateTime<FixedOffset>>, String> {
+        self.now
+            .as_deref()
+            .map(DateTime::parse_from_rfc3339)
+            .transpose()
+            .map_err(|err| format!("now: {err}"))
+    }
+
+    /// Score a model's raw `response`.  Numbers must fall within the field's tolerance, strings
+    /// must match ignoring case and surrounding whitespace, and anything else must match exactly.
+    pub fn check(&self, question: &Question, response: &str) -> Result<(), String> {
+        let response: serde_json::Value =
+            serde_json::from_str(response).map_err(|err| format!("response is not JSON: {err}"))?;
+        question.validate(&response)?;
+        for (field, expected) in self.expected.iter() {
+            let actual = response.get(field).unwrap_or(&serde_json::Value::Null);
+            let matches = match (expected, actual) {
+                (serde_json::Value::Number(e), serde_json::Value::Number(a)) => {
+                    let tolerance = self.tolerance.get(field).copied().unwrap_or(0.0);
+                    match (e.as_f64(), a.as_f64()) {
+                        (Some(e), Some(a)) => (e - a).abs() <= tolerance + EPSILON,
+                        _ => false,
+                    }
+                }
+                (serde_json::Value::String(e), serde_json::Value::String(a)) => {
+                    e.trim().eq_ignore_ascii_case(a.trim())
+                }
+                _ => expected == actual,
+            };
+            if !matches {
+                return Err(format!("{field}: expected {expected}, got {actual}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read a corpus:  one case per line, as JSON.  Blank lines are skipped.
+pub fn load_corpus(path: &str) -> Result<Vec<Case>, String> {
+    let corpus = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut cases = vec![];
+    for (idx, line) in corpus.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let case: Case =
+            serde_json::from_str(line).map_err(|err| format!("line {}: {err}", idx + 1))?;
+        case.now()
+            .map_err(|err| format!("line {}: {err}", idx + 1))?;
+        cases.push(case);
+    }
+    Ok(cases)
+}
+
+///////////////////////////////////////////// Recording ////////////////////////////////////////////
+
+/// A model's raw response to one case, kept so an evaluation can be rerun without the model.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Recording {
+    pub model: String,
+    pub question: String,
+    /// The `prompt_hash` of the question's system prompt.
+    pub prompt: String,
+    pub answer: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub now: Option<String>,
+    pub response: String,
+}
+
+type RecordingKey = (String, String, String, String, Option<String>);
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recordings {
+    responses: BTreeMap<RecordingKey, String>,
+}
+
+impl Recordings {
+    /// Read the recordings at `path`, one per line.  A missing file has no recordings.  When the
+    /// same case was recorded more than once, the latest recording wins.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let recordings = match std::fs::read_to_string(path) {
+            Ok(recordings) => recordings,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.to_string()),
+        };
+        let mut responses = BTreeMap::new();
+        for (idx, line) in recordings.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let recording: Recording =
+                serde_json::from_str(line).map_err(|err| format!("line {}: {err}", idx + 1))?;
+            responses.insert(
+                (
+                    recording.model,
+                    recording.question,
+                    recording.prompt,
+                    recording.answer,
+                    recording.now,
+                ),
+                recording.response,
+            );
+        }
+        Ok(Self { responses })
+    }
+
+    /// Every model with at least one recording.
+    pub fn models(&self) -> Vec<String> {
+        let mut models = self
+            .responses
+            .keys()
+            .map(|key| key.0.clone())
+            .collect::<Vec<_>>();
+        models.dedup();
+        models
+    }
+
+    pub fn get(&self, model: &str, question: &Question, case: &Case) -> Option<&str> {
+        self.responses
+            .get(&(
+                model.to_string(),
+                question.slug.clone(),
+                question.prompt_hash(),
+                case.answer.clone(),
+                case.now.clone(),
+            ))
+            .map(String::as_str)
+    }
+}
+
+fn append_recording(path: &str, recording: &Recording) {
+    let mut output = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .expect("could not open recordings");
+    let recording = serde_json::to_string(recording).expect("recording should always serialize");
+    writeln!(output, "{recording}").expect("could not append to recordings");
+}
+
+/// Ask the ollama at `host` to answer `case` to `question`, returning the raw response.
+async fn generate(
+    host: &str,
+    model: &str,
+    question: &Question,
+    case: &Case,
+) -> Result<String, String> {
+    let now = case.now()?.unwrap_or_else(|| Local::now().fixed_offset());
+    question.generate(host, model, &case.answer, now).await
+}
+
+/////////////////////////////////////////////// Score //////////////////////////////////////////////
+
+/// How one version of one question's prompt fared with one model.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Score {
+    pub question: String,
+    pub prompt: String,
+    pub model: String,
+    pub passed: usize,
+    pub failed: usize,
+    /// Cases with no recorded response when running offline.
+    pub unrecorded: usize,
+}
+
+impl Score {
+    pub fn accuracy(&self) -> Option<f64> {
+        let scored = self.passed + self.failed;
+        if scored == 0 {
+            None
+        } else {
+            Some(self.passed as f64 / scored as f64)
+        }
+    }
+
+    /// The score in `baseline` for the same question and model, under any version of the prompt.
+    pub fn baseline<'a>(&self, baseline: &'a [Score]) -> Option<&'a Score> {
+        baseline
+            .iter()
+            .find(|b| b.question == self.question && b.model == self.model)
+    }
+
+    /// True when accuracy fell from `baseline`.
+    pub fn regressed(&self, baseline: &[Score]) -> bool {
+        match (
+            self.accuracy(),
+            self.baseline(baseline).and_then(Score::accuracy),
+        ) {
+            (Some(accuracy), Some(before)) => accuracy + EPSILON < before,
+            _ => false,
+        }
+    }
+}
+
+/// Render `scores` as a table, with each score's change from `baseline`.
+pub fn render(scores: &[Score], baseline: &[Score]) -> String {
+    let percent = |accuracy: Option<f64>| {
+        accuracy
+            .map(|a| format!("{:.1}%", a * 100.0))
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let mut out = format!(
+        "{:<28} {:<20} {:>5} {:>6} {:>8} {:>8} {:>8}\n",
+        "prompt", "model", "cases", "passed", "accuracy", "baseline", "change"
+    );
+    for score in scores {
+        let before = score.baseline(baseline).and_then(Score::accuracy);
+        let change = match (score.accuracy(), before) {
+            (Some(accuracy), Some(before)) => format!("{:+.1}", (accuracy - before) * 100.0),
+            _ => "-".to_string(),
+        };
+        out += &format!(
+            "{:<28} {:<20} {:>5} {:>6} {:>8} {:>8} {:>8}",
+            format!("{}@{}", score.question, score.prompt),
+            score.model,
+            score.passed + score.failed,
+            score.passed,
+            percent(score.accuracy()),
+            percent(before),
+            change,
+        );
+        if score.unrecorded > 0 {
+            out += &format!("  ({} unrecorded)", score.unrecorded);
+        }
+        if score.regressed(baseline) {
+            out += "  REGRESSED";
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    arrrg_derive::CommandLine,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct EvalOptions {
+    #[arrrg(
+        optional,
+        "Comma-separated questionnaires whose prompts to compare; defaults to $NOTAPSYCH_QUESTIONS or the builtin questions."
+    )]
+    pub questions: String,
+    #[arrrg(
+        optional,
+        "Comma-separated models to evaluate; defaults to $NOTAPSYCH_MODEL, or every recorded model when offline."
+    )]
+    pub models: String,
+    #[arrrg(
+        optional,
+        "Model responses to replay when offline, and to record to otherwise."
+    )]
+    pub recordings: String,
+    #[arrrg(flag, "Score recorded responses only; never call a model.")]
+    pub offline: bool,
+    #[arrrg(optional, "Scores saved by an earlier run to compare against.")]
+    pub baseline: String,
+    #[arrrg(optional, "Save this run's scores, to use as a later baseline.")]
+    pub save: String,
+    #[arrrg(flag, "Print every case that fails and why.")]
+    pub verbose: bool,
+}
+
+fn list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn env(var: &str) -> String {
+    match std::env::var(var) {
+        Ok(value) => value,
+        Err(_) => {
+            eprintln!("please set {var} in your environment or run --offline");
+            std::process::exit(13);
+        }
+    }
+}
+
+pub async fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.get(1).map(String::as_str) == Some("generate") {
+        synthetic::main(&args[2..]);
+        return;
+    }
+    let (options, free) = EvalOptions::from_command_line_relaxed(USAGE);
+    if free.is_empty() {
+        eprintln!("{USAGE}");
+        std::process::exit(13);
+    }
+    let mut cases = vec![];
+    for path in free.iter() {
+        match load_corpus(path) {
+            Ok(corpus) => cases.extend(corpus),
+            Err(err) => {
+                eprintln!("could not load corpus {path}: {err}");
+                std::process::exit(13);
+            }
+        }
+    }
+    let questionnaires = if options.questions.is_empty() {
+        vec![Questionnaire::from_env()]
+    } else {
+        list(&options.questions)
+            .iter()
+            .map(|path| {
+                Questionnaire::load(path).unwrap_or_else(|err| {
+                    eprintln!("could not load questions from {path}: {err}");
+                    std::process::exit(13);
+                })
+            })
+            .collect()
+    };
+    if options.offline && options.recordings.is_empty() {
+        eprintln!("--offline replays --recordings; pass the recordings to replay");
+        std::process::exit(13);
+    }
+    let recordings = if options.recordings.is_empty() {
+        Recordings::default()
+    } else {
+        Recordings::load(&options.recordings).unwrap_or_else(|err| {
+            eprintln!("could not load recordings {}: {err}", options.recordings);
+            std::process::exit(13);
+        })
+    };
+    let models = if !options.models.is_empty() {
+        list(&options.models)
+    } else if options.offline {
+        recordings.models()
+    } else {
+        vec![env("NOTAPSYCH_MODEL")]
+    };
+    let host = if options.offline {
+        String::new()
+    } else {
+        env("OLLAMA_HOST")
+    };
+    let baseline: Vec<Score> = if options.baseline.is_empty() {
+        vec![]
+    } else {
+        std::fs::read_to_string(&options.baseline)
+            .map_err(|err| err.to_string())
+            .and_then(|scores| serde_json::from_str(&scores).map_err(|err| err.to_string()))
+            .unwrap_or_else(|err| {
+                eprintln!("could not load baseline {}: {err}", options.baseline);
+                std::process::exit(13);
+            })
+    };
+    let mut scores = vec![];
+    for questionnaire in questionnaires.iter() {
+        for model in models.iter() {
+            let mut by_question: BTreeMap<&str, Score> = BTreeMap::new();
+            for case in cases.iter() {
+                let Some(question) = questionnaire.get(&case.question) else {
+                    continue;
+                };
+                if question.now && case.now.is_none() {
+                    eprintln!(
+                        "{} depends on the time; give its case {:?} a now",
+                        question.slug, case.answer
+                    );
+                    std::process::exit(13);
+                }
+                let score = by_question.entry(&question.slug).or_insert_with(|| Score {
+                    question: question.slug.clone(),
+                    prompt: question.prompt_hash(),
+                    model: model.clone(),
+                    ..Score::default()
+                });
+                let response = if options.offline {
+                    match recordings.get(model, question, case) {
+                        Some(response) => Ok(response.to_string()),
+                        None => {
+                            score.unrecorded += 1;
+                            continue;
+                        }
+                    }
+                } else {
+                    let response = generate(&host, model, question, case).await;
+                    if let (Ok(response), false) = (&response, options.recordings.is_empty()) {
+                        append_recording(
+                            &options.recordings,
+                            &Recording {
+                                model: model.clone(),
+                                question: question.slug.clone(),
+                                prompt: question.prompt_hash(),
+                                answer: case.answer.clone(),
+                                now: case.now.clone(),
+                                response: response.clone(),
+                            },
+                        );
+                    }
+                    response
+                };
+                match response.and_then(|response| case.check(question, &response)) {
+                    Ok(()) => score.passed += 1,
+                    Err(err) => {
+                        score.failed += 1;
+                        if options.verbose {
+                            eprintln!("{} {model} {:?}: {err}", question.slug, case.answer);
+                        }
+                    }
+                }
+            }
+            scores.extend(by_question.into_values());
+        }
+    }
+    print!("{}", render(&scores, &baseline));
+    if !options.save.is_empty() {
+        let saved = serde_json::to_string_pretty(&scores).expect("scores should always serialize");
+        if let Err(err) = std::fs::write(&options.save, saved) {
+            eprintln!("could not write {}: {err}", options.save);
+            std::process::exit(13);
+        }
+    }
+    if scores.iter().any(|score| score.regressed(&baseline)) {
+        std::process::exit(13);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(json: serde_json::Value) -> Case {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn question(slug: &str) -> Question {
+        Questionnaire::builtin().get(slug).unwrap().clone()
+    }
+
+    #[test]
+    fn check_numbers_within_tolerance() {
+        let question = question("slept-how-long");
+        let case = case(serde_json::json!({
+            "question": "slept-how-long", "answer": "about 7 and a half hours",
+            "expected": {"sleep_hours": 7.5}, "tolerance": {"sleep_hours": 0.25},
+        }));
+        let response =
+            |hours: f64| serde_json::json!({"sleep_hours": hours, "justification": ""}).to_string();
+        assert_eq!(Ok(()), case.check(&question, &response(7.5)));
+        assert_eq!(Ok(()), case.check(&question, &response(7.75)));
+        assert!(case.check(&question, &response(8.0)).is_err());
+    }
+
+    #[test]
+    fn check_strings_ignore_case() {
+        let question = question("medication");
+        let case = case(serde_json::json!({
+            "question": "medication", "answer": "two beers",
+            "expected": {"substance": "beer", "quantity": 2, "units": "beer"},
+        }));
+        let response = |substance: &str, quantity: f64| {
+            serde_json::json!({
+                "substance": substance, "quantity": quantity, "units": "beer",
+                "frequency": "once", "times": 1, "justification": "",
+            })
+            .to_string()
+        };
+        assert_eq!(Ok(()), case.check(&question, &response(" Beer", 2.0)));
+        assert!(case.check(&question, &response("wine", 2.0)).is_err());
+        assert!(case.check(&question, &response("beer", 2.5)).is_err());
+    }
+
+    #[test]
+    fn check_rejects_invalid_responses() {
+        let question = question("slept-how-long");
+        let case = case(serde_json::json!({
+            "question": "slept-how-long", "answer": "7",
+            "expected": {"sleep_hours": 7},
+        }));
+        assert!(case.check(&question, "seven").is_err());
+        assert!(case.check(&question, r#"{"sleep_hours": 7}"#).is_err());
+        assert!(case.check(&question, r#"{"justification": ""}"#).is_err());
+    }
+
+    #[test]
+    fn checked_in_corpus_loads() {
+        let questionnaire = Questionnaire::builtin();
+        let cases = load_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/evals/checkin.jsonl"))
+            .expect("the checked-in corpus should load");
+        assert!(!cases.is_empty());
+        for case in cases.iter() {
+            assert!(
+                questionnaire.get(&case.question).is_some(),
+                "unknown question {}",
+                case.question
+            );
+            assert!(case.now().is_ok(), "{case:?}");
+        }
+    }
+}
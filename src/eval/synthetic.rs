@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+
+use arrrg::CommandLine;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Timelike};
+
+use super::{list, Case};
+
+const USAGE: &str =
+    "USAGE: notapsych-eval generate [--seed N] [--count N] [--questions SLUG,SLUG] [--typos PERCENT]";
+
+/// The questions answers can be generated for.
+pub const QUESTIONS: &[&str] = &[
+    "medication",
+    "drinking-episode",
+    "slept-how-long",
+    "last-slept",
+    "hydration",
+];
+
+/// Drinks by how they are said in the singular and plural, and the serving the medication
+/// question asks the model to count them in.
+const DRINKS: &[(&str, &str, &str)] = &[
+    ("beer", "beers", "beer"),
+    ("IPA", "IPAs", "beer"),
+    ("cold one", "cold ones", "beer"),
+    ("brewski", "brewskis", "beer"),
+    ("glass of wine", "glasses of wine", "glass"),
+    ("glass of vino", "glasses of vino", "glass"),
+    ("shot", "shots", "shot"),
+    ("shot of whiskey", "shots of whiskey", "shot"),
+    ("margarita", "margaritas", "cocktail"),
+    ("vodka soda", "vodka sodas", "cocktail"),
+];
+
+const OCCASIONS: &[&str] = &[
+    "",
+    "",
+    " at the work thing",
+    " with dinner",
+    " after work",
+    " at the game",
+    " last night",
+    " w/ friends",
+];
+
+const NUMBERS: &[&str] = &[
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    "eleven", "twelve",
+];
+
+/// Other words that carry a quantity or a time the labels depend on.
+const QUANTITIES: &[&str] = &["half", "couple", "noon", "midnight"];
+
+/// Timezones the answers are given in.
+const OFFSETS: &[i32] = &[-8, -5, 0, 1];
+
+//////////////////////////////////////////////// Rng ///////////////////////////////////////////////
+
+/// SplitMix64:  small, and the same on every machine and every build, so a seed always
+/// reproduces its corpus.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// True with probability `p`.
+    pub fn chance(&mut self, p: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+///////////////////////////////////////////// Phrasing /////////////////////////////////////////////
+
+/// Say a small count the way people do:  as digits, as a word, or as "a".
+fn count(rng: &mut Rng, n: usize) -> String {
+    match (n, rng.below(3)) {
+        (1, 0) => "a".to_string(),
+        (2, 0) => "a couple".to_string(),
+        (n, 1) if n < NUMBERS.len() => NUMBERS[n].to_string(),
+        (n, _) => n.to_string(),
+    }
+}
+
+/// Say a time of day in one of the many ways people write it.
+fn clock(rng: &mut Rng, time: DateTime<FixedOffset>) -> String {
+    let (hour, minute) = (time.hour(), time.minute());
+    let h12 = match hour % 12 {
+        0 => 12,
+        h => h,
+    };
+    let meridiem = if hour < 12 { "am" } else { "pm" };
+    match (hour, minute, rng.below(5)) {
+        (0, 0, 0) => "midnight".to_string(),
+        (12, 0, 0) => "noon".to_string(),
+        (_, 0, 0 | 1) => h12.to_string(),
+        (_, 0, 2) => format!("{h12}{meridiem}"),
+        (_, 0, 3) => format!("{h12} {}", meridiem.to_uppercase()),
+        (_, 30, 0) => format!("half {h12}"),
+        (_, _, 4) => format!("{hour:02}:{minute:02}"),
+        (_, _, 1) => format!("{h12}:{minute:02}"),
+        _ => format!("{h12}:{minute:02}{meridiem}"),
+    }
+}
+
+/// Say a number of hours, returning the phrase and how precisely it was said.
+fn hours(rng: &mut Rng, hours: f64) -> (String, f64) {
+    let whole = hours.trunc() as usize;
+    let half = hours.fract() >= 0.5;
+    match (half, rng.below(4)) {
+        (false, 0) => (format!("{whole}h"), 0.0),
+        (false, 1) => (format!("{whole} hrs"), 0.0),
+        (false, 2) => (format!("{}ish hours", count(rng, whole)), 0.5),
+        (false, _) => (format!("{} hours", count(rng, whole)), 0.0),
+        (true, 0) => (format!("{whole}.5 hours"), 0.0),
+        (true, 1) => (format!("{}h30", whole), 0.0),
+        (true, _) => (format!("{} and a half hours", count(rng, whole)), 0.0),
+    }
+}
+
+/// True when `word` says a number, spelled out ("seven", "sevenish") or otherwise ("half").
+fn says_a_number(word: &str) -> bool {
+    let word = word.to_lowercase();
+    NUMBERS.iter().any(|n| word.starts_with(n)) || QUANTITIES.contains(&word.as_str())
+}
+
+/// Misspell one word of `answer`.  Numbers, spelled out or not, and short words are left alone
+/// so the labels stay true.
+fn typo(rng: &mut Rng, answer: &str) -> String {
+    let mut words = answer.split(' ').map(str::to_string).collect::<Vec<_>>();
+    let candidates = words
+        .iter()
+        .enumerate()
+        .filter(|(_, w)| w.len() >= 4 && w.chars().all(|c| c.is_ascii_alphabetic()))
+        .filter(|(_, w)| !says_a_number(w))
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        return answer.to_string();
+    }
+    let idx = *rng.pick(&candidates);
+    let mut chars = words[idx].chars().collect::<Vec<_>>();
+    let at = 1 + rng.below(chars.len() - 2);
+    match rng.below(3) {
+        0 => chars.swap(at, at + 1),
+        1 => {
+            chars.remove(at);
+        }
+        _ => chars.insert(at, chars[at]),
+    }
+    words[idx] = chars.into_iter().collect();
+    words.join(" ")
+}
+
+/// A moment to answer at:  some day in 2025, on the hour or half hour, between `earliest` and
+/// `latest` o'clock.
+fn moment(rng: &mut Rng, earliest: u32, latest: u32) -> DateTime<FixedOffset> {
+    let offset = FixedOffset::east_opt(rng.pick(OFFSETS) * 3_600).expect("offsets are in range");
+    let day = NaiveDate::from_ymd_opt(2025, 1, 1).expect("2025-01-01 is a day")
+        + Duration::days(rng.below(365) as i64);
+    let hour = earliest + rng.below((latest - earliest + 1) as usize) as u32;
+    let minute = 30 * rng.below(2) as u32;
+    day.and_hms_opt(0, 0, 0)
+        .expect("midnight is a time")
+        .and_local_timezone(offset)
+        .single()
+        .expect("fixed offsets are never ambiguous")
+        + Duration::minutes((hour * 60 + minute) as i64)
+}
+
+fn hours_between(start: DateTime<FixedOffset>, end: DateTime<FixedOffset>) -> f64 {
+    (end - start).num_minutes() as f64 / 60.0
+}
+
+///////////////////////////////////////////// Generate /////////////////////////////////////////////
+
+/// Generate one labeled answer to `question`, misspelling a word with probability `typos`.
+pub fn generate(rng: &mut Rng, question: &str, typos: f64) -> Case {
+    let mut expected = serde_json::Map::new();
+    let mut tolerance = BTreeMap::new();
+    let mut now = None;
+    let answer = match question {
+        "medication" => {
+            // Drinks counted in the same serving, so the answer has one true quantity and unit.
+            let (_, _, units) = *rng.pick(DRINKS);
+            let kinds = DRINKS
+                .iter()
+                .filter(|(_, _, u)| *u == units)
+                .collect::<Vec<_>>();
+            let mut drinks = vec![];
+            let mut quantity = 0;
+            for _ in 0..1 + rng.below(2) {
+                let (singular, plural, _) = **rng.pick(&kinds);
+                let n = 1 + rng.below(4);
+                let said = count(rng, n);
+                drinks.push(match (n, said.as_str()) {
+                    (1, "a") if singular.starts_with(['a', 'e', 'i', 'o', 'u', 'I']) => {
+                        format!("an {singular}")
+                    }
+                    (1, _) => format!("{said} {singular}"),
+                    _ => format!("{said} {plural}"),
+                });
+                quantity += n;
+            }
+            expected.insert("quantity".to_string(), quantity.into());
+            expected.insert("units".to_string(), units.into());
+            expected.insert("frequency".to_string(), "once".into());
+            format!(
+                "{}{}",
+                drinks.join(*rng.pick(&[" and ", ", ", " + ", " & "])),
+                rng.pick(OCCASIONS)
+            )
+        }
+        "drinking-episode" => {
+            let asked = moment(rng, 20, 26);
+            let duration = 0.5 * (1 + rng.below(12)) as f64;
+            let since_last = 0.5 * rng.below(6) as f64;
+            let last = asked - Duration::minutes((since_last * 60.0) as i64);
+            let first = last - Duration::minutes((duration * 60.0) as i64);
+            now = Some(asked);
+            expected.insert(
+                "started_hours_ago".to_string(),
+                hours_between(first, asked).into(),
+            );
+            expected.insert("duration_hours".to_string(), duration.into());
+            let (first, last) = (clock(rng, first), clock(rng, last));
+            let (answer, slack) = match rng.below(4) {
+                0 => (format!("started around {first}, last one at {last}"), 0.5),
+                1 => (format!("had drinks from {first} to {last}"), 0.25),
+                2 => (format!("{first} till {last}"), 0.25),
+                _ => (format!("from about {first} until {last}ish"), 0.5),
+            };
+            tolerance.insert("started_hours_ago".to_string(), slack);
+            tolerance.insert("duration_hours".to_string(), slack);
+            answer
+        }
+        "slept-how-long" => {
+            let woke = moment(rng, 5, 10);
+            let slept = 0.5 * (8 + rng.below(13)) as f64;
+            expected.insert("sleep_hours".to_string(), slept.into());
+            if rng.chance(0.5) {
+                let (said, slack) = hours(rng, slept);
+                tolerance.insert("sleep_hours".to_string(), slack.max(0.25));
+                format!("{}{said}", rng.pick(&["", "about ", "maybe ", "slept "]))
+            } else {
+                let fell = woke - Duration::minutes((slept * 60.0) as i64);
+                let (fell, woke) = (clock(rng, fell), clock(rng, woke));
+                let (answer, slack) = match rng.below(4) {
+                    0 => (format!("crashed around {fell}, up at {woke}ish"), 0.5),
+                    1 => (format!("went to bed at {fell} and woke up at {woke}"), 0.25),
+                    2 => (format!("slept {fell} to {woke}"), 0.25),
+                    _ => (format!("fell asleep at {fell}, got up at {woke}"), 0.25),
+                };
+                tolerance.insert("sleep_hours".to_string(), slack);
+                answer
+            }
+        }
+        "last-slept" => {
+            let asked = moment(rng, 10, 22);
+            let yesterday = rng.chance(0.2);
+            let woke = asked
+                .with_hour(5 + rng.below(5) as u32)
+                .expect("5-9 is an hour")
+                - Duration::days(yesterday as i64);
+            now = Some(asked);
+            expected.insert("awake_hours".to_string(), hours_between(woke, asked).into());
+            let woke = clock(rng, woke);
+            let (answer, slack) = match (yesterday, rng.below(3)) {
+                (true, 0) => (format!("{woke} yesterday"), 0.25),
+                (true, _) => (format!("yesterday around {woke}"), 0.5),
+                (false, 0) => (format!("woke up at {woke}"), 0.25),
+                (false, 1) => (format!("up since {woke}"), 0.25),
+                (false, _) => (format!("got up around {woke} this morning"), 0.5),
+            };
+            tolerance.insert("awake_hours".to_string(), slack);
+            answer
+        }
+        "hydration" => {
+            let n = 1 + rng.below(8);
+            let (answer, quantity, units) = match rng.below(5) {
+                0 => (format!("{} glasses", count(rng, n.max(2))), n.max(2), "cup"),
+                1 => (
+                    format!("{} cups of water", count(rng, n.max(2))),
+                    n.max(2),
+                    "cup",
+                ),
+                2 => (
+                    format!("{} bottles", count(rng, n.max(2))),
+                    n.max(2),
+                    "bottle",
+                ),
+                3 => (format!("{}oz", 8 * n), 8 * n, "oz"),
+                _ => (format!("{} ml", 250 * n), 250 * n, "mL"),
+            };
+            expected.insert("quantity".to_string(), quantity.into());
+            expected.insert("units".to_string(), units.into());
+            answer
+        }
+        question => panic!("cannot generate answers to {question}"),
+    };
+    let answer = if rng.chance(typos) {
+        typo(rng, &answer)
+    } else {
+        answer
+    };
+    Case {
+        question: question.to_string(),
+        answer,
+        now: now.map(|now| now.to_rfc3339()),
+        expected,
+        tolerance,
+    }
+}
+
+#[derive(
+    Clone, Debug, Eq, PartialEq, arrrg_derive::CommandLine, serde::Deserialize, serde::Serialize,
+)]
+#[serde(default)]
+pub struct GenerateOptions {
+    #[arrrg(optional, "Seed; the same seed always generates the same corpus.")]
+    pub seed: u64,
+    #[arrrg(optional, "How many answers to generate.")]
+    pub count: usize,
+    #[arrrg(
+        optional,
+        "Comma-separated questions to answer; defaults to every question answers can be generated for."
+    )]
+    pub questions: String,
+    #[arrrg(optional, "Percent of answers to misspell a word in.")]
+    pub typos: u64,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            count: 100,
+            questions: String::new(),
+            typos: 10,
+        }
+    }
+}
+
+pub fn main(args: &[String]) {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (options, free) = GenerateOptions::from_arguments_relaxed(USAGE, &args);
+    if !free.is_empty() {
+        eprintln!("command takes no positional arguments");
+        std::process::exit(13);
+    }
+    let questions = if options.questions.is_empty() {
+        QUESTIONS.iter().map(|q| q.to_string()).collect()
+    } else {
+        list(&options.questions)
+    };
+    if let Some(question) = questions.iter().find(|q| !QUESTIONS.contains(&q.as_str())) {
+        eprintln!(
+            "cannot generate answers to {question}; expected one of {}",
+            QUESTIONS.join(", ")
+        );
+        std::process::exit(13);
+    }
+    let mut rng = Rng::new(options.seed);
+    for _ in 0..options.count {
+        let question = rng.pick(&questions).clone();
+        let case = generate(&mut rng, &question, options.typos as f64 / 100.0);
+        println!(
+            "{}",
+            serde_json::to_string(&case).expect("case should always serialize")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::questionnaire::Questionnaire;
+
+    fn corpus(seed: u64) -> Vec<Case> {
+        let mut rng = Rng::new(seed);
+        (0..50)
+            .map(|idx| generate(&mut rng, QUESTIONS[idx % QUESTIONS.len()], 0.1))
+            .collect()
+    }
+
+    #[test]
+    fn splitmix64_reference() {
+        let mut rng = Rng::new(0);
+        assert_eq!(0xe220a8397b1dcdaf, rng.next_u64());
+        assert_eq!(0x6e789e6aa1b965f4, rng.next_u64());
+    }
+
+    #[test]
+    fn typo_leaves_numbers_alone() {
+        let mut rng = Rng::new(1);
+        for _ in 0..100 {
+            let answer = "seven and a half hours";
+            let typo = typo(&mut rng, answer);
+            assert!(typo.starts_with("seven and a half "), "{typo}");
+            assert_ne!(answer, typo);
+        }
+        let answer = "twelve sevenish half couple noon midnight";
+        assert_eq!(answer, typo(&mut rng, answer));
+    }
+
+    #[test]
+    fn same_seed_same_corpus() {
+        assert_eq!(corpus(42), corpus(42));
+        assert_ne!(corpus(42), corpus(43));
+    }
+
+    #[test]
+    fn cases_fit_their_questions() {
+        let questionnaire = Questionnaire::builtin();
+        for case in corpus(7) {
+            assert!(questionnaire.get(&case.question).is_some(), "{case:?}");
+            assert!(!case.answer.trim().is_empty(), "{case:?}");
+            assert!(!case.expected.is_empty(), "{case:?}");
+            assert!(case.now().is_ok(), "{case:?}");
+        }
+    }
+}
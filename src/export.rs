@@ -0,0 +1,514 @@
+use std::collections::BTreeMap;
+
+use arrrg::CommandLine;
+use chrono::DateTime;
+
+use crate::query::{parse_day, read_log_lines};
+use crate::transcript::{Dose, Filter, LogLine};
+
+const LOINC: &str = "http://loinc.org";
+const UCUM: &str = "http://unitsofmeasure.org";
+const OBSERVATION_CATEGORY: &str = "http://terminology.hl7.org/CodeSystem/observation-category";
+/// Observation category for answers the user gave.
+const SURVEY: &str = "survey";
+/// Observation category for what a device measured.
+const ACTIVITY: &str = "activity";
+/// Observation category for alcohol and other habits.
+const SOCIAL_HISTORY: &str = "social-history";
+
+/// LOINC sleep duration.
+const LOINC_SLEEP_DURATION: &str = "93832-4";
+/// LOINC PHQ-9 total score.
+const LOINC_PHQ_9_TOTAL: &str = "44261-6";
+/// LOINC GAD-7 total score.
+const LOINC_GAD_7_TOTAL: &str = "70274-6";
+
+//////////////////////////////////////////////// CSV ///////////////////////////////////////////////
+
+/// One table of a CSV export:  every line of one kind, flattened.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<BTreeMap<String, String>>,
+}
+
+impl Table {
+    pub fn render(&self) -> String {
+        let mut out = self
+            .columns
+            .iter()
+            .map(|c| csv_field(c))
+            .collect::<Vec<_>>()
+            .join(",");
+        out.push('\n');
+        for row in self.rows.iter() {
+            out += &self
+                .columns
+                .iter()
+                .map(|c| csv_field(row.get(c).map(String::as_str).unwrap_or("")))
+                .collect::<Vec<_>>()
+                .join(",");
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Flatten `log_lines` into one table per kind.  Nested objects become dotted columns (e.g.
+/// `dose.quantity`), lists of plain values are joined with "; ", and anything more deeply
+/// structured is kept as JSON in a single cell.
+pub fn tables(log_lines: &[LogLine]) -> BTreeMap<&'static str, Table> {
+    let mut tables: BTreeMap<&'static str, Table> = BTreeMap::new();
+    for log_line in log_lines {
+        let value = serde_json::to_value(log_line).expect("log line should always serialize");
+        let mut row = BTreeMap::new();
+        flatten("", &value, &mut row);
+        row.remove("type");
+        let table = tables.entry(log_line.kind()).or_default();
+        for (column, _) in row.iter() {
+            if !table.columns.contains(column) {
+                table.columns.push(column.clone());
+            }
+        }
+        table.rows.push(row);
+    }
+    for table in tables.values_mut() {
+        // recorded_at first so every table joins on the same leading column.
+        table.columns.sort_by_key(|c| c != "recorded_at");
+    }
+    tables
+}
+
+fn flatten(prefix: &str, value: &serde_json::Value, row: &mut BTreeMap<String, String>) {
+    let cell = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => String::new(),
+        value => value.to_string(),
+    };
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten(&key, value, row);
+            }
+        }
+        serde_json::Value::Array(values)
+            if values.iter().all(|v| !v.is_object() && !v.is_array()) =>
+        {
+            row.insert(
+                prefix.to_string(),
+                values.iter().map(cell).collect::<Vec<_>>().join("; "),
+            );
+        }
+        value => {
+            row.insert(prefix.to_string(), cell(value));
+        }
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+//////////////////////////////////////////////// FHIR //////////////////////////////////////////////
+
+/// Map sleep, alcohol, medication and screening entries to a FHIR R4 collection Bundle of
+/// Observation and MedicationStatement resources.  Entries with no clinical counterpart are left
+/// out.
+pub fn fhir_bundle(log_lines: &[LogLine]) -> serde_json::Value {
+    let entry = log_lines
+        .iter()
+        .filter_map(fhir_resource)
+        .map(|resource| serde_json::json! {{ "resource": resource }})
+        .collect::<Vec<_>>();
+    serde_json::json! {{
+        "resourceType": "Bundle",
+        "type": "collection",
+        "entry": entry,
+    }}
+}
+
+fn fhir_resource(log_line: &LogLine) -> Option<serde_json::Value> {
+    let hours = |value: f64| {
+        serde_json::json! {{
+            "value": value,
+            "unit": "h",
+            "system": UCUM,
+            "code": "h",
+        }}
+    };
+    let category = if log_line.is_measured() {
+        ACTIVITY
+    } else {
+        SURVEY
+    };
+    match log_line {
+        LogLine::HoursSlept { sleep_hours, .. } => Some(observation(
+            log_line,
+            category,
+            code(Some(LOINC_SLEEP_DURATION), "Sleep duration"),
+            "valueQuantity",
+            hours(*sleep_hours),
+        )),
+        LogLine::LastSlept { awake_hours, .. } => Some(observation(
+            log_line,
+            category,
+            code(None, "Hours awake since last sleep"),
+            "valueQuantity",
+            hours(*awake_hours),
+        )),
+        LogLine::SleepQuality { answer, .. } => Some(observation(
+            log_line,
+            category,
+            code(None, "Self-reported sleep quality (0-10)"),
+            "valueQuantity",
+            serde_json::json! {{
+                "value": answer,
+                "unit": "{score}",
+                "system": UCUM,
+                "code": "{score}",
+            }},
+        )),
+        LogLine::Screening {
+            instrument,
+            total,
+            severity,
+            ..
+        } => {
+            let loinc = match instrument.as_str() {
+                "PHQ-9" => Some(LOINC_PHQ_9_TOTAL),
+                "GAD-7" => Some(LOINC_GAD_7_TOTAL),
+                _ => None,
+            };
+            let mut observation = observation(
+                log_line,
+                SURVEY,
+                code(loinc, &format!("{instrument} total score")),
+                "valueInteger",
+                (*total).into(),
+            );
+            observation["interpretation"] = serde_json::json! {[{ "text": severity }]};
+            Some(observation)
+        }
+        LogLine::Alcohol {
+            standard_drinks,
+            started_at,
+            duration_hours,
+            ..
+        } => {
+            let mut observation = observation(
+                log_line,
+                SOCIAL_HISTORY,
+                code(None, "Alcohol consumed (standard drinks)"),
+                "valueQuantity",
+                serde_json::json! {{
+                    "value": standard_drinks,
+                    "unit": "{drink}",
+                    "system": UCUM,
+                    "code": "{drink}",
+                }},
+            );
+            if let Ok(start) = DateTime::parse_from_rfc3339(started_at) {
+                let end = start + chrono::Duration::minutes((duration_hours * 60.0) as i64);
+                let object = observation
+                    .as_object_mut()
+                    .expect("an observation should be an object");
+                object.remove("effectiveDateTime");
+                object.insert(
+                    "effectivePeriod".to_string(),
+                    serde_json::json! {{
+                        "start": start.to_rfc3339(),
+                        "end": end.to_rfc3339(),
+                    }},
+                );
+            }
+            Some(observation)
+        }
+        LogLine::Medication {
+            recorded_at,
+            substance,
+            dose,
+            justification,
+            ..
+        } => {
+            let status = match dose {
+                Dose::Once { .. } => "completed",
+                Dose::Unknown { .. } => "unknown",
+                _ => "active",
+            };
+            let mut dosage = serde_json::json! {{
+                "text": dose.summary(),
+                "doseAndRate": [{
+                    "doseQuantity": {
+                        "value": dose.quantity(),
+                        "unit": dose.units(),
+                    },
+                }],
+            }};
+            let repeat = |frequency: f64, period_unit: &str| {
+                serde_json::json! {{
+                    "repeat": {
+                        "frequency": frequency.round().max(1.0) as u64,
+                        "period": 1,
+                        "periodUnit": period_unit,
+                    },
+                }}
+            };
+            match dose {
+                Dose::Daily { times_daily, .. } => dosage["timing"] = repeat(*times_daily, "d"),
+                Dose::Weekly { times_weekly, .. } => dosage["timing"] = repeat(*times_weekly, "wk"),
+                Dose::Monthly { times_monthly, .. } => {
+                    dosage["timing"] = repeat(*times_monthly, "mo")
+                }
+                Dose::AsNeeded { .. } => dosage["asNeededBoolean"] = true.into(),
+                _ => {}
+            }
+            Some(serde_json::json! {{
+                "resourceType": "MedicationStatement",
+                "status": status,
+                "medicationCodeableConcept": { "text": substance },
+                "subject": { "display": "self" },
+                "effectiveDateTime": recorded_at,
+                "dateAsserted": recorded_at,
+                "dosage": [dosage],
+                "note": [{ "text": justification }],
+            }})
+        }
+        _ => None,
+    }
+}
+
+fn code(loinc: Option<&str>, text: &str) -> serde_json::Value {
+    match loinc {
+        Some(loinc) => serde_json::json! {{
+            "coding": [{ "system": LOINC, "code": loinc, "display": text }],
+            "text": text,
+        }},
+        None => serde_json::json! {{ "text": text }},
+    }
+}
+
+fn observation(
+    log_line: &LogLine,
+    category: &str,
+    code: serde_json::Value,
+    value_type: &str,
+    value: serde_json::Value,
+) -> serde_json::Value {
+    let mut observation = serde_json::json! {{
+        "resourceType": "Observation",
+        "status": "final",
+        "category": [{
+            "coding": [{ "system": OBSERVATION_CATEGORY, "code": category }],
+        }],
+        "code": code,
+        "subject": { "display": "self" },
+        "effectiveDateTime": log_line.recorded_at(),
+    }};
+    observation[value_type] = value;
+    if !log_line.justification().is_empty() {
+        observation["note"] = serde_json::json! {[{ "text": log_line.justification() }]};
+    }
+    observation
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    arrrg_derive::CommandLine,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct ExportOptions {
+    #[arrrg(optional, "Transcript to read; defaults to $NOTAPSYCH_TRANSCRIPT.")]
+    pub transcript: String,
+    #[arrrg(optional, "Export format: csv or fhir.")]
+    pub format: String,
+    #[arrrg(optional, "Directory to write one CSV file per entry type into.")]
+    pub output: String,
+    #[arrrg(optional, "Export entries recorded on or after this day (YYYY-MM-DD).")]
+    pub since: String,
+    #[arrrg(
+        optional,
+        "Export entries recorded on or before this day (YYYY-MM-DD)."
+    )]
+    pub until: String,
+}
+
+pub fn main(args: &[String]) {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (options, free) = ExportOptions::from_arguments_relaxed(
+        "USAGE: notapsych export --format csv --output DIR | --format fhir [--since DAY] [--until DAY]",
+        &args,
+    );
+    if !free.is_empty() {
+        eprintln!("command takes no positional arguments");
+        std::process::exit(13);
+    }
+    let filter = Filter {
+        since: parse_day("--since", &options.since),
+        until: parse_day("--until", &options.until),
+        ..Filter::default()
+    };
+    let log_lines = read_log_lines(&options.transcript)
+        .into_iter()
+        .filter(|log_line| filter.matches(log_line))
+        .collect::<Vec<_>>();
+    match options.format.as_str() {
+        "csv" => {
+            if options.output.is_empty() {
+                eprintln!("--format csv writes one file per entry type; pass --output DIR");
+                std::process::exit(13);
+            }
+            if let Err(err) = std::fs::create_dir_all(&options.output) {
+                eprintln!("could not create {}: {err}", options.output);
+                std::process::exit(13);
+            }
+            for (kind, table) in tables(&log_lines) {
+                let path = format!("{}/{kind}.csv", options.output);
+                if let Err(err) = std::fs::write(&path, table.render()) {
+                    eprintln!("could not write {path}: {err}");
+                    std::process::exit(13);
+                }
+            }
+        }
+        "fhir" => println!(
+            "{}",
+            serde_json::to_string_pretty(&fhir_bundle(&log_lines))
+                .expect("bundle should always serialize")
+        ),
+        format => {
+            eprintln!("unknown format {format:?}; expected csv or fhir");
+            std::process::exit(13);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{records, Column, CsvMapping};
+
+    fn hours_slept(recorded_at: &str, sleep_hours: f64, measured: bool) -> LogLine {
+        LogLine::HoursSlept {
+            recorded_at: recorded_at.to_string(),
+            sleep_hours,
+            justification: "said \"about\" 7, maybe\nmore".to_string(),
+            imported_from: None,
+            measured,
+            provenance: vec![],
+        }
+    }
+
+    #[test]
+    fn csv_round_trips_through_records() {
+        let log_lines = vec![
+            hours_slept("2025-06-09T07:00:00-07:00", 7.5, false),
+            hours_slept("2025-06-10T06:30:00-07:00", 6.25, true),
+        ];
+        let tables = tables(&log_lines);
+        let table = &tables["hours-slept"];
+        assert_eq!("recorded_at", table.columns[0]);
+        let mut parsed = records(&table.render(), ',');
+        let header = parsed.remove(0);
+        assert_eq!(table.columns, header);
+        let rows = parsed
+            .into_iter()
+            .map(|record| header.iter().cloned().zip(record).collect())
+            .map(|row: BTreeMap<String, String>| {
+                row.into_iter().filter(|(_, v)| !v.is_empty()).collect()
+            })
+            .collect::<Vec<BTreeMap<String, String>>>();
+        assert_eq!(table.rows, rows);
+    }
+
+    #[test]
+    fn csv_export_imports_back() {
+        let log_lines = vec![
+            hours_slept("2025-06-09T07:00:00-07:00", 7.5, false),
+            hours_slept("2025-06-10T06:30:00-07:00", 6.25, false),
+        ];
+        let csv = tables(&log_lines)["hours-slept"].render();
+        let column = |column: &str| Column {
+            column: column.to_string(),
+            format: String::new(),
+            scale: 1.0,
+        };
+        let mapping = CsvMapping {
+            source: "notapsych".to_string(),
+            delimiter: None,
+            wake: column("recorded_at"),
+            onset: None,
+            sleep_hours: Some(column("sleep_hours")),
+            sleep_quality: None,
+        };
+        let (imported, problems) = mapping.convert(&csv);
+        assert!(problems.is_empty(), "{problems:?}");
+        let imported = imported
+            .iter()
+            .filter_map(|log_line| match log_line {
+                LogLine::HoursSlept {
+                    recorded_at,
+                    sleep_hours,
+                    ..
+                } => Some((recorded_at.clone(), *sleep_hours)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("2025-06-09T07:00:00-07:00".to_string(), 7.5),
+                ("2025-06-10T06:30:00-07:00".to_string(), 6.25),
+            ],
+            imported
+        );
+    }
+
+    #[test]
+    fn fhir_categories() {
+        let bundle = fhir_bundle(&[
+            hours_slept("2025-06-09T07:00:00-07:00", 7.5, false),
+            hours_slept("2025-06-10T06:30:00-07:00", 6.25, true),
+        ]);
+        let category = |idx: usize| {
+            bundle["entry"][idx]["resource"]["category"][0]["coding"][0]["code"].clone()
+        };
+        assert_eq!("survey", category(0));
+        assert_eq!("activity", category(1));
+    }
+
+    #[test]
+    fn fhir_includes_alcohol() {
+        let bundle = fhir_bundle(&[LogLine::Alcohol {
+            recorded_at: "2025-06-10T02:00:00-07:00".to_string(),
+            standard_drinks: 4.0,
+            started_at: "2025-06-09T20:00:00-07:00".to_string(),
+            duration_hours: 4.0,
+            reported: None,
+            justification: String::new(),
+            provenance: vec![],
+        }]);
+        let resource = &bundle["entry"][0]["resource"];
+        assert_eq!("Observation", resource["resourceType"]);
+        assert_eq!(4.0, resource["valueQuantity"]["value"]);
+        assert_eq!(
+            "2025-06-10T00:00:00-07:00",
+            resource["effectivePeriod"]["end"]
+        );
+        assert_eq!(
+            "social-history",
+            resource["category"][0]["coding"][0]["code"]
+        );
+    }
+}
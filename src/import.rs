@@ -0,0 +1,576 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use arrrg::CommandLine;
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone};
+
+pub mod apple_health;
+pub mod fitbit;
+
+use crate::query::{append_log_line, read_log_lines, transcript_path};
+use crate::transcript::{LogLine, Provenance};
+
+const USAGE: &str = "USAGE: notapsych import csv|apple-health|fitbit [OPTIONS] FILE...";
+
+////////////////////////////////////////////// Column //////////////////////////////////////////////
+
+/// Where to find one value in a CSV row and how to read it.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Column {
+    pub column: String,
+    /// For times, a chrono format string; RFC 3339 when empty.  Times without an offset are
+    /// local.  For durations, one of "hours" (the default), "minutes", "seconds" or "h:mm".
+    #[serde(default)]
+    pub format: String,
+    /// Multiply numbers by this, e.g. 0.1 to turn an 85% quality into 8.5 out of 10.
+    #[serde(default = "Column::one")]
+    pub scale: f64,
+}
+
+impl Column {
+    fn one() -> f64 {
+        1.0
+    }
+
+    fn cell<'a>(
+        &self,
+        header: &BTreeMap<&str, usize>,
+        row: &'a [String],
+    ) -> Result<&'a str, String> {
+        let idx = header
+            .get(self.column.as_str())
+            .ok_or_else(|| format!("no column named {:?}", self.column))?;
+        Ok(row.get(*idx).map(|cell| cell.trim()).unwrap_or(""))
+    }
+
+    pub fn time(
+        &self,
+        header: &BTreeMap<&str, usize>,
+        row: &[String],
+    ) -> Result<DateTime<FixedOffset>, String> {
+        let cell = self.cell(header, row)?;
+        parse_time(cell, &self.format).map_err(|err| format!("{}: {cell:?}: {err}", self.column))
+    }
+
+    pub fn hours(&self, header: &BTreeMap<&str, usize>, row: &[String]) -> Result<f64, String> {
+        let cell = self.cell(header, row)?;
+        let hours = match self.format.as_str() {
+            "" | "hours" => number(cell)?,
+            "minutes" => number(cell)? / 60.0,
+            "seconds" => number(cell)? / 3_600.0,
+            "h:mm" => match cell.split_once(':') {
+                Some((h, m)) => number(h)? + number(m)? / 60.0,
+                None => return Err(format!("{}: {cell:?} is not h:mm", self.column)),
+            },
+            format => return Err(format!("unknown duration format {format:?}")),
+        };
+        Ok(hours * self.scale)
+    }
+
+    pub fn number(&self, header: &BTreeMap<&str, usize>, row: &[String]) -> Result<f64, String> {
+        Ok(number(self.cell(header, row)?)? * self.scale)
+    }
+}
+
+fn number(cell: &str) -> Result<f64, String> {
+    cell.trim()
+        .trim_end_matches('%')
+        .trim()
+        .parse::<f64>()
+        .map_err(|err| format!("{cell:?}: {err}"))
+}
+
+/// Parse a timestamp in `format` (RFC 3339 when empty), reading times without an offset as local.
+pub fn parse_time(cell: &str, format: &str) -> Result<DateTime<FixedOffset>, String> {
+    if format.is_empty() {
+        return DateTime::parse_from_rfc3339(cell).map_err(|err| err.to_string());
+    }
+    if let Ok(when) = DateTime::parse_from_str(cell, format) {
+        return Ok(when);
+    }
+    let naive = NaiveDateTime::parse_from_str(cell, format).map_err(|err| err.to_string())?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|when| when.fixed_offset())
+        .ok_or_else(|| "no such local time".to_string())
+}
+
+///////////////////////////////////////////// CsvMapping ///////////////////////////////////////////
+
+/// How the columns of another tracker's CSV export map onto transcript entries, e.g.
+///
+/// ```json
+/// {"source": "sleep-cycle", "delimiter": ";",
+///  "wake": {"column": "End", "format": "%Y-%m-%d %H:%M:%S"},
+///  "sleep_hours": {"column": "Time asleep (seconds)", "format": "seconds"},
+///  "sleep_quality": {"column": "Sleep Quality", "scale": 0.1}}
+/// ```
+///
+/// Each row is one night recorded at its wake time:  a `last-slept` entry with no hours awake,
+/// plus `hours-slept` and `sleep-quality` entries for whichever columns are mapped.  Without a
+/// `sleep_hours` column, hours slept is the time from `onset` to `wake`.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct CsvMapping {
+    pub source: String,
+    #[serde(default)]
+    pub delimiter: Option<char>,
+    pub wake: Column,
+    #[serde(default)]
+    pub onset: Option<Column>,
+    #[serde(default)]
+    pub sleep_hours: Option<Column>,
+    #[serde(default)]
+    pub sleep_quality: Option<Column>,
+}
+
+impl CsvMapping {
+    pub fn load(path: &str) -> Result<Self, std::io::Error> {
+        let mapping = std::fs::read_to_string(path)?;
+        serde_json::from_str(&mapping)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    /// Convert every row of `csv` into log lines.  Rows that cannot be read are reported by
+    /// line number and skipped.
+    pub fn convert(&self, csv: &str) -> (Vec<LogLine>, Vec<String>) {
+        let mut rows = records(csv, self.delimiter.unwrap_or(','));
+        if rows.is_empty() {
+            return (vec![], vec!["no header row".to_string()]);
+        }
+        let names = rows.remove(0);
+        let header = names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| (name.trim(), idx))
+            .collect::<BTreeMap<_, _>>();
+        let mut log_lines = vec![];
+        let mut problems = vec![];
+        for (idx, row) in rows.iter().enumerate() {
+            if row.iter().all(|cell| cell.trim().is_empty()) {
+                continue;
+            }
+            // Row 1 is the header.
+            match self.row(&header, row, idx + 2) {
+                Ok(converted) => log_lines.extend(converted),
+                Err(err) => problems.push(format!("row {}: {err}", idx + 2)),
+            }
+        }
+        (log_lines, problems)
+    }
+
+    fn row(
+        &self,
+        header: &BTreeMap<&str, usize>,
+        row: &[String],
+        number: usize,
+    ) -> Result<Vec<LogLine>, String> {
+        let wake = self.wake.time(header, row)?;
+        let recorded_at = wake.to_rfc3339();
+        let record = row.join(&self.delimiter.unwrap_or(',').to_string());
+        let provenance = |kind: &str| vec![Provenance::imported(kind, &record, &self.source)];
+        let imported_from = Some(self.source.clone());
+        let justification = format!("Imported from {} row {number}.", self.source);
+        let sleep_hours = match (&self.sleep_hours, &self.onset) {
+            (Some(column), _) => Some(column.hours(header, row)?),
+            (None, Some(onset)) => {
+                let onset = onset.time(header, row)?;
+                Some((wake - onset).num_minutes() as f64 / 60.0)
+            }
+            (None, None) => None,
+        };
+        let mut log_lines = vec![LogLine::LastSlept {
+            recorded_at: recorded_at.clone(),
+            awake_hours: 0.0,
+            justification: justification.clone(),
+            imported_from: imported_from.clone(),
+            measured: false,
+            provenance: provenance("last-slept"),
+        }];
+        if let Some(sleep_hours) = sleep_hours {
+            if !(0.0..=24.0).contains(&sleep_hours) {
+                return Err(format!("{sleep_hours} hours of sleep is implausible"));
+            }
+            log_lines.push(LogLine::HoursSlept {
+                recorded_at: recorded_at.clone(),
+                sleep_hours,
+                justification: justification.clone(),
+                imported_from: imported_from.clone(),
+                measured: false,
+                provenance: provenance("hours-slept"),
+            });
+        }
+        if let Some(column) = &self.sleep_quality {
+            let answer = column.number(header, row)?;
+            if !(0.0..=10.0).contains(&answer) {
+                return Err(format!(
+                    "quality {answer} is outside 0..=10; check its scale"
+                ));
+            }
+            log_lines.push(LogLine::SleepQuality {
+                recorded_at,
+                answer,
+                justification,
+                imported_from,
+                measured: false,
+                provenance: provenance("sleep-quality"),
+            });
+        }
+        Ok(log_lines)
+    }
+}
+
+/// Split CSV text into records, honoring double-quoted fields that contain the delimiter,
+/// doubled quotes, or newlines.
+pub fn records(csv: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = csv.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/////////////////////////////////////////////// Night //////////////////////////////////////////////
+
+/// One night of device-measured sleep.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Night {
+    pub source: String,
+    pub onset: DateTime<FixedOffset>,
+    pub wake: DateTime<FixedOffset>,
+    pub asleep_hours: f64,
+}
+
+impl Night {
+    /// A `last-slept` entry with no hours awake and an `hours-slept` entry, both at wake time and
+    /// marked as measured.
+    pub fn log_lines(&self) -> Vec<LogLine> {
+        let recorded_at = self.wake.to_rfc3339();
+        let justification = format!(
+            "Measured by {} from {} to {}.",
+            self.source,
+            self.onset.to_rfc3339(),
+            recorded_at
+        );
+        let provenance =
+            |kind: &str| vec![Provenance::imported(kind, &justification, &self.source)];
+        vec![
+            LogLine::LastSlept {
+                recorded_at: recorded_at.clone(),
+                awake_hours: 0.0,
+                justification: justification.clone(),
+                imported_from: Some(self.source.clone()),
+                measured: true,
+                provenance: provenance("last-slept"),
+            },
+            LogLine::HoursSlept {
+                recorded_at,
+                sleep_hours: self.asleep_hours,
+                justification: justification.clone(),
+                imported_from: Some(self.source.clone()),
+                measured: true,
+                provenance: provenance("hours-slept"),
+            },
+        ]
+    }
+}
+
+//////////////////////////////////////////// de-duplication ////////////////////////////////////////
+
+/// Imported lines recorded within this many seconds of each other are the same record.
+const SAME_RECORD_SECONDS: i64 = 3_600;
+
+/// The identity of a line for de-duplication:  its kind, where it was imported from, and when it
+/// was recorded (for imported sleep, the wake time) rounded to the nearest hour.  Trackers
+/// disagree by a few minutes about the wake time from one export to the next, so re-importing a
+/// night still matches, while split sleep and naps that wake on the same day stay separate.
+fn identity(log_line: &LogLine) -> Option<(&'static str, Option<String>, i64)> {
+    let recorded_at = log_line.timestamp()?.timestamp();
+    Some((
+        log_line.kind(),
+        log_line.imported_from().map(str::to_string),
+        (recorded_at + SAME_RECORD_SECONDS / 2).div_euclid(SAME_RECORD_SECONDS),
+    ))
+}
+
+/// Drop the lines of `imported` that are already in `existing`, or repeated within `imported`.
+pub fn deduplicate(existing: &[LogLine], imported: Vec<LogLine>) -> (Vec<LogLine>, usize) {
+    let mut seen = existing
+        .iter()
+        .filter_map(identity)
+        .collect::<BTreeSet<_>>();
+    let before = imported.len();
+    let fresh = imported
+        .into_iter()
+        .filter(|log_line| match identity(log_line) {
+            Some(identity) => seen.insert(identity),
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    let duplicates = before - fresh.len();
+    (fresh, duplicates)
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    arrrg_derive::CommandLine,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct ImportOptions {
+    #[arrrg(
+        optional,
+        "Transcript to append to; defaults to $NOTAPSYCH_TRANSCRIPT."
+    )]
+    pub transcript: String,
+    #[arrrg(optional, "JSON column mapping for CSV imports.")]
+    pub mapping: String,
+    #[arrrg(
+        flag,
+        "Print the entries that would be imported instead of importing them."
+    )]
+    pub dry_run: bool,
+}
+
+/// Append `imported` to the transcript, less what is already there, and say what happened.
+fn finish(options: &ImportOptions, imported: Vec<LogLine>, problems: Vec<String>) {
+    for problem in problems.iter() {
+        eprintln!("skipping {problem}");
+    }
+    let path = transcript_path(&options.transcript);
+    // Importing into a transcript that does not exist yet is how a new one gets started.
+    let existing = if std::path::Path::new(&path).exists() {
+        read_log_lines(&path)
+    } else {
+        vec![]
+    };
+    let (fresh, duplicates) = deduplicate(&existing, imported);
+    for log_line in fresh.iter() {
+        if options.dry_run {
+            println!(
+                "{}",
+                serde_json::to_string(log_line).expect("log line should always serialize")
+            );
+        } else {
+            append_log_line(&path, log_line);
+        }
+    }
+    eprintln!(
+        "{} {} entries, skipped {duplicates} duplicates and {} unreadable records",
+        if options.dry_run {
+            "would import"
+        } else {
+            "imported"
+        },
+        fresh.len(),
+        problems.len()
+    );
+}
+
+pub fn main(args: &[String]) {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (options, free) = ImportOptions::from_arguments_relaxed(USAGE, &args);
+    let (format, paths) = match free.as_slice() {
+        [format, paths @ ..] if !paths.is_empty() => (format.as_str(), paths),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(13);
+        }
+    };
+    let mapping = match format {
+        "csv" if options.mapping.is_empty() => {
+            eprintln!("csv imports need a column mapping; pass --mapping FILE");
+            std::process::exit(13);
+        }
+        "csv" => Some(CsvMapping::load(&options.mapping).unwrap_or_else(|err| {
+            eprintln!("could not load mapping from {}: {err}", options.mapping);
+            std::process::exit(13);
+        })),
+        "apple-health" | "fitbit" => None,
+        format => {
+            eprintln!("unknown import format {format:?}; expected csv, apple-health or fitbit");
+            std::process::exit(13);
+        }
+    };
+    let mut imported = vec![];
+    let mut problems = vec![];
+    for path in paths {
+        let unreadable = |err: std::io::Error| -> ! {
+            eprintln!("could not read {path}: {err}");
+            std::process::exit(13);
+        };
+        let (log_lines, trouble) = match (format, &mapping) {
+            // Apple Health exports run to gigabytes; stream them rather than read them whole.
+            ("apple-health", _) => match std::fs::File::open(path) {
+                Ok(file) => apple_health::convert(std::io::BufReader::new(file)),
+                Err(err) => unreadable(err),
+            },
+            (format, mapping) => {
+                let input = std::fs::read_to_string(path).unwrap_or_else(|err| unreadable(err));
+                match (format, mapping) {
+                    ("csv", Some(mapping)) => mapping.convert(&input),
+                    _ => fitbit::convert(&input),
+                }
+            }
+        };
+        imported.extend(log_lines);
+        problems.extend(
+            trouble
+                .into_iter()
+                .map(|problem| format!("{path}: {problem}")),
+        );
+    }
+    finish(&options, imported, problems);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(json: &str) -> CsvMapping {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn hours(log_lines: &[LogLine]) -> Vec<f64> {
+        log_lines
+            .iter()
+            .filter_map(|log_line| match log_line {
+                LogLine::HoursSlept { sleep_hours, .. } => Some(*sleep_hours),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn records_honor_quotes() {
+        let csv = "a,b\r\n\"1,5\",\"say \"\"hi\"\"\nthere\"\n";
+        assert_eq!(
+            vec![
+                vec!["a".to_string(), "b".to_string()],
+                vec!["1,5".to_string(), "say \"hi\"\nthere".to_string()],
+            ],
+            records(csv, ',')
+        );
+    }
+
+    #[test]
+    fn convert_sleep_cycle() {
+        let mapping = mapping(
+            r#"{"source": "sleep-cycle", "delimiter": ";",
+                "wake": {"column": "End", "format": "%Y-%m-%d %H:%M:%S%z"},
+                "sleep_hours": {"column": "Asleep", "format": "seconds"},
+                "sleep_quality": {"column": "Quality", "scale": 0.1}}"#,
+        );
+        let csv = "End;Asleep;Quality\n2025-06-10 07:00:00-0700;27000;85%\n;;\n";
+        let (log_lines, problems) = mapping.convert(csv);
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(3, log_lines.len());
+        assert_eq!(vec![7.5], hours(&log_lines));
+        assert!(log_lines
+            .iter()
+            .all(|log_line| log_line.imported_from() == Some("sleep-cycle")));
+        for log_line in log_lines.iter() {
+            let [provenance] = log_line.provenance() else {
+                panic!("expected one provenance, got {:?}", log_line.provenance());
+            };
+            assert_eq!(log_line.kind(), provenance.question);
+            assert_eq!("2025-06-10 07:00:00-0700;27000;85%", provenance.answer);
+            assert_eq!("sleep-cycle", provenance.model);
+            assert_eq!(Provenance::IMPORT, provenance.backend);
+        }
+        match &log_lines[2] {
+            LogLine::SleepQuality { answer, .. } => assert!((answer - 8.5).abs() < 1e-9),
+            log_line => panic!("expected sleep-quality, got {}", log_line.kind()),
+        }
+    }
+
+    #[test]
+    fn convert_onset_and_problems() {
+        let mapping = mapping(
+            r#"{"source": "sheet",
+                "wake": {"column": "wake"},
+                "onset": {"column": "onset"}}"#,
+        );
+        let csv = "onset,wake\n\
+            2025-06-09T23:30:00-07:00,2025-06-10T06:30:00-07:00\n\
+            2025-06-10T23:30:00-07:00,yesterday\n";
+        let (log_lines, problems) = mapping.convert(csv);
+        assert_eq!(vec![7.0], hours(&log_lines));
+        assert_eq!(1, problems.len());
+        assert!(problems[0].starts_with("row 3:"), "{problems:?}");
+    }
+
+    #[test]
+    fn h_mm_durations() {
+        let column = Column {
+            column: "asleep".to_string(),
+            format: "h:mm".to_string(),
+            scale: 1.0,
+        };
+        let header = BTreeMap::from([("asleep", 0)]);
+        assert_eq!(Ok(7.25), column.hours(&header, &["7:15".to_string()]));
+        assert!(column.hours(&header, &["7.25".to_string()]).is_err());
+    }
+
+    #[test]
+    fn deduplicate_by_wake_time() {
+        let night = |source: &str, wake: &str| Night {
+            source: source.to_string(),
+            onset: DateTime::parse_from_rfc3339("2025-06-09T23:00:00-07:00").unwrap(),
+            wake: DateTime::parse_from_rfc3339(wake).unwrap(),
+            asleep_hours: 7.0,
+        };
+        let existing = night("fitbit", "2025-06-10T07:00:00-07:00").log_lines();
+        // The same night exported again with a wake time a few minutes off.
+        let again = night("fitbit", "2025-06-10T07:04:00-07:00").log_lines();
+        let (fresh, duplicates) = deduplicate(&existing, again);
+        assert_eq!((0, 2), (fresh.len(), duplicates));
+        // Another source for the same night is not a duplicate, nor is the next night.
+        let mut imported = night("apple-health", "2025-06-10T07:00:00-07:00").log_lines();
+        imported.extend(night("fitbit", "2025-06-11T07:00:00-07:00").log_lines());
+        let (fresh, duplicates) = deduplicate(&existing, imported);
+        assert_eq!((4, 0), (fresh.len(), duplicates));
+    }
+
+    #[test]
+    fn deduplicate_keeps_split_sleep() {
+        let sleep = |onset: &str, wake: &str| Night {
+            source: "fitbit".to_string(),
+            onset: DateTime::parse_from_rfc3339(onset).unwrap(),
+            wake: DateTime::parse_from_rfc3339(wake).unwrap(),
+            asleep_hours: 3.5,
+        };
+        let mut imported =
+            sleep("2025-06-09T23:00:00-07:00", "2025-06-10T03:00:00-07:00").log_lines();
+        imported
+            .extend(sleep("2025-06-10T04:00:00-07:00", "2025-06-10T07:30:00-07:00").log_lines());
+        imported
+            .extend(sleep("2025-06-10T14:00:00-07:00", "2025-06-10T15:00:00-07:00").log_lines());
+        let (fresh, duplicates) = deduplicate(&[], imported);
+        assert_eq!((6, 0), (fresh.len(), duplicates));
+    }
+}
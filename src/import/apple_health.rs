@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+use chrono::{DateTime, FixedOffset};
+
+use super::{parse_time, Night};
+use crate::transcript::LogLine;
+
+const SLEEP_ANALYSIS: &str = "HKCategoryTypeIdentifierSleepAnalysis";
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+/// Asleep segments separated by less than this belong to the same night.
+const MAX_GAP_HOURS: i64 = 2;
+
+/// When an asleep segment started and ended.
+type Segment = (DateTime<FixedOffset>, DateTime<FixedOffset>);
+
+/// The value of attribute `name` on the tag in `line`, unescaped.
+fn attribute(line: &str, name: &str) -> Option<String> {
+    let start = line.find(&format!(" {name}=\""))? + name.len() + 3;
+    let end = start + line[start..].find('"')?;
+    Some(
+        line[start..end]
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+/// Group each source's asleep segments into nights.  In-bed and awake segments are not sleep
+/// and are ignored; overlapping segments are only counted once.
+pub fn nights(segments: BTreeMap<String, Vec<Segment>>) -> Vec<Night> {
+    let mut nights = vec![];
+    for (source, mut segments) in segments {
+        segments.sort();
+        let mut night: Option<Night> = None;
+        for (start, end) in segments {
+            if let Some(current) = night.as_mut() {
+                if start - current.wake <= chrono::Duration::hours(MAX_GAP_HOURS) {
+                    let counted_from = start.max(current.wake);
+                    if end > counted_from {
+                        current.asleep_hours += (end - counted_from).num_seconds() as f64 / 3_600.0;
+                    }
+                    current.wake = current.wake.max(end);
+                    continue;
+                }
+                nights.extend(night.take());
+            }
+            night = Some(Night {
+                source: format!("apple-health:{source}"),
+                onset: start,
+                wake: end,
+                asleep_hours: (end - start).num_seconds() as f64 / 3_600.0,
+            });
+        }
+        nights.extend(night);
+    }
+    nights.sort_by_key(|night| night.wake);
+    nights
+}
+
+/// Convert the sleep analysis records of an Apple Health `export.xml` into measured sleep
+/// entries.  Exports run to gigabytes, almost all of it other record types, so rather than parse
+/// the XML this streams it a line at a time looking for sleep analysis `<Record>` tags, which
+/// Health writes with their attributes on one line.
+pub fn convert(xml: impl BufRead) -> (Vec<LogLine>, Vec<String>) {
+    let mut segments: BTreeMap<String, Vec<Segment>> = BTreeMap::new();
+    let mut problems = vec![];
+    for (idx, line) in xml.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                problems.push(format!("line {}: {err}; stopping", idx + 1));
+                break;
+            }
+        };
+        let line = line.as_str();
+        if !line.contains("<Record ") || !line.contains(SLEEP_ANALYSIS) {
+            continue;
+        }
+        let value = attribute(line, "value").unwrap_or_default();
+        if !value.contains("Asleep") {
+            continue;
+        }
+        let source = attribute(line, "sourceName").unwrap_or_else(|| "unknown".to_string());
+        let times = (
+            attribute(line, "startDate").map(|t| parse_time(&t, TIME_FORMAT)),
+            attribute(line, "endDate").map(|t| parse_time(&t, TIME_FORMAT)),
+        );
+        match times {
+            (Some(Ok(start)), Some(Ok(end))) if start <= end => {
+                segments.entry(source).or_default().push((start, end));
+            }
+            _ => problems.push(format!("line {}: unreadable sleep record", idx + 1)),
+        }
+    }
+    let log_lines = nights(segments).iter().flat_map(Night::log_lines).collect();
+    (log_lines, problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(value: &str, start: &str, end: &str) -> String {
+        format!(
+            concat!(
+                r#" <Record type="{}" sourceName="Watch" "#,
+                r#"startDate="2025-06-{} -0700" endDate="2025-06-{} -0700" value="{}"/>"#,
+            ),
+            SLEEP_ANALYSIS, start, end, value
+        )
+    }
+
+    #[test]
+    fn streams_segments_into_nights() {
+        let xml = [
+            "<HealthData>".to_string(),
+            record(
+                "HKCategoryValueSleepAnalysisInBed",
+                "09 22:30:00",
+                "10 07:00:00",
+            ),
+            record(
+                "HKCategoryValueSleepAnalysisAsleepCore",
+                "09 23:00:00",
+                "10 03:00:00",
+            ),
+            record(
+                "HKCategoryValueSleepAnalysisAwake",
+                "10 03:00:00",
+                "10 03:30:00",
+            ),
+            record(
+                "HKCategoryValueSleepAnalysisAsleepDeep",
+                "10 03:30:00",
+                "10 06:30:00",
+            ),
+            r#" <Record type="HKQuantityTypeIdentifierStepCount" value="12"/>"#.to_string(),
+            record(
+                "HKCategoryValueSleepAnalysisAsleepCore",
+                "10 23:00:00",
+                "yesterday",
+            ),
+            "</HealthData>".to_string(),
+        ]
+        .join("\n");
+        let (log_lines, problems) = convert(xml.as_bytes());
+        assert_eq!(1, problems.len(), "{problems:?}");
+        assert!(problems[0].starts_with("line 7:"), "{problems:?}");
+        match log_lines.as_slice() {
+            [LogLine::LastSlept { .. }, LogLine::HoursSlept {
+                sleep_hours,
+                imported_from,
+                measured: true,
+                ..
+            }] => {
+                assert_eq!(7.0, *sleep_hours);
+                assert_eq!(Some("apple-health:Watch"), imported_from.as_deref());
+            }
+            _ => panic!("expected one night, got {log_lines:?}"),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use super::{parse_time, Night};
+use crate::transcript::LogLine;
+
+/// Fitbit writes local times without an offset.
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f";
+
+/// The fields of a Fitbit sleep log this import uses.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SleepLog {
+    start_time: String,
+    end_time: String,
+    minutes_asleep: f64,
+    /// False for naps.  Logs without it are taken to be main sleep.
+    #[serde(default = "main_sleep")]
+    main_sleep: bool,
+}
+
+fn main_sleep() -> bool {
+    true
+}
+
+/// Convert a Fitbit sleep export (`sleep-YYYY-MM-DD.json`, a JSON list of sleep logs) into
+/// measured sleep entries.  Naps are skipped; the night is the log Fitbit marks as main sleep.
+pub fn convert(json: &str) -> (Vec<LogLine>, Vec<String>) {
+    let logs: Vec<serde_json::Value> = match serde_json::from_str(json) {
+        Ok(logs) => logs,
+        Err(err) => return (vec![], vec![format!("not a list of sleep logs: {err}")]),
+    };
+    let mut log_lines = vec![];
+    let mut problems = vec![];
+    for (idx, log) in logs.into_iter().enumerate() {
+        let night = serde_json::from_value::<SleepLog>(log)
+            .map_err(|err| err.to_string())
+            .and_then(|log| {
+                if !log.main_sleep {
+                    return Ok(None);
+                }
+                Ok(Some(Night {
+                    source: "fitbit".to_string(),
+                    onset: parse_time(&log.start_time, TIME_FORMAT)?,
+                    wake: parse_time(&log.end_time, TIME_FORMAT)?,
+                    asleep_hours: log.minutes_asleep / 60.0,
+                }))
+            });
+        match night {
+            Ok(Some(night)) => log_lines.extend(night.log_lines()),
+            Ok(None) => {}
+            Err(err) => problems.push(format!("sleep log {}: {err}", idx + 1)),
+        }
+    }
+    (log_lines, problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn naps_are_skipped() {
+        let json = r#"[
+            {"startTime": "2025-06-09T23:00:00.000", "endTime": "2025-06-10T07:00:00.000",
+             "minutesAsleep": 420, "mainSleep": true},
+            {"startTime": "2025-06-10T14:00:00.000", "endTime": "2025-06-10T15:00:00.000",
+             "minutesAsleep": 50, "mainSleep": false},
+            {"startTime": "2025-06-10T23:30:00.000", "endTime": "2025-06-11T06:30:00.000",
+             "minutesAsleep": 390}
+        ]"#;
+        let (log_lines, problems) = convert(json);
+        assert!(problems.is_empty(), "{problems:?}");
+        let hours = log_lines
+            .iter()
+            .filter_map(|log_line| match log_line {
+                LogLine::HoursSlept { sleep_hours, .. } => Some(*sleep_hours),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![7.0, 6.5], hours);
+    }
+}
@@ -0,0 +1,307 @@
+use arrrg::CommandLine;
+use chrono::Local;
+use rustyline::error::ReadlineError;
+use rustyline::history::History;
+use rustyline::{Editor, Helper};
+
+use crate::query::append_log_line;
+use crate::transcript::{LogLine, Provenance};
+
+/// The frequency scale PHQ-9 and GAD-7 share, scored 0 through 3.
+const FREQUENCY: &[&str] = &[
+    "Not at all",
+    "Several days",
+    "More than half the days",
+    "Nearly every day",
+];
+
+///////////////////////////////////////////// Instrument ///////////////////////////////////////////
+
+/// A standardized fixed-choice questionnaire.  Every item is answered by choosing one of
+/// `choices`, scored by its position, and the total is the sum of the item scores.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instrument {
+    pub name: &'static str,
+    pub stem: &'static str,
+    pub items: &'static [&'static str],
+    pub choices: &'static [&'static str],
+    /// The lowest total in each severity band, in increasing order.
+    pub bands: &'static [(u32, &'static str)],
+    /// An item whose nonzero answer warrants printing the accompanying message.
+    pub alert: Option<(usize, &'static str)>,
+}
+
+pub const PHQ_9: Instrument = Instrument {
+    name: "PHQ-9",
+    stem: "Over the last 2 weeks, how often have you been bothered by any of the following problems?",
+    items: &[
+        "Little interest or pleasure in doing things",
+        "Feeling down, depressed, or hopeless",
+        "Trouble falling or staying asleep, or sleeping too much",
+        "Feeling tired or having little energy",
+        "Poor appetite or overeating",
+        "Feeling bad about yourself - or that you are a failure or have let yourself or your family down",
+        "Trouble concentrating on things, such as reading the newspaper or watching television",
+        "Moving or speaking so slowly that other people could have noticed, or the opposite - being so fidgety or restless that you have been moving around a lot more than usual",
+        "Thoughts that you would be better off dead, or of hurting yourself in some way",
+    ],
+    choices: FREQUENCY,
+    bands: &[
+        (0, "minimal"),
+        (5, "mild"),
+        (10, "moderate"),
+        (15, "moderately severe"),
+        (20, "severe"),
+    ],
+    alert: Some((
+        8,
+        "You said you have had thoughts of being better off dead or of hurting yourself.
+Please talk to someone you trust or a professional today.  In the US, call or text 988 to reach the
+Suicide & Crisis Lifeline; elsewhere, contact your local emergency number.",
+    )),
+};
+
+pub const GAD_7: Instrument = Instrument {
+    name: "GAD-7",
+    stem: "Over the last 2 weeks, how often have you been bothered by the following problems?",
+    items: &[
+        "Feeling nervous, anxious, or on edge",
+        "Not being able to stop or control worrying",
+        "Worrying too much about different things",
+        "Trouble relaxing",
+        "Being so restless that it is hard to sit still",
+        "Becoming easily annoyed or irritable",
+        "Feeling afraid, as if something awful might happen",
+    ],
+    choices: FREQUENCY,
+    bands: &[
+        (0, "minimal"),
+        (5, "mild"),
+        (10, "moderate"),
+        (15, "severe"),
+    ],
+    alert: None,
+};
+
+pub const INSTRUMENTS: &[Instrument] = &[PHQ_9, GAD_7];
+
+impl Instrument {
+    /// Look up an instrument by name, ignoring case and punctuation, so "phq9" finds PHQ-9.
+    pub fn find(name: &str) -> Option<Self> {
+        let key = |name: &str| {
+            name.chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect::<String>()
+                .to_lowercase()
+        };
+        INSTRUMENTS
+            .iter()
+            .find(|instrument| key(instrument.name) == key(name))
+            .copied()
+    }
+
+    pub fn max_total(&self) -> u32 {
+        (self.items.len() * self.choices.len().saturating_sub(1)) as u32
+    }
+
+    /// Sum the item scores, rejecting a response set that does not fit the instrument.
+    pub fn score(&self, responses: &[u8]) -> Result<u32, String> {
+        if responses.len() != self.items.len() {
+            return Err(format!(
+                "{} has {} items, but {} responses were given",
+                self.name,
+                self.items.len(),
+                responses.len()
+            ));
+        }
+        if let Some(response) = responses
+            .iter()
+            .find(|&&r| r as usize >= self.choices.len())
+        {
+            return Err(format!(
+                "{}: response {response} is outside 0..{}",
+                self.name,
+                self.choices.len()
+            ));
+        }
+        Ok(responses.iter().map(|&r| r as u32).sum())
+    }
+
+    pub fn severity(&self, total: u32) -> &'static str {
+        self.bands
+            .iter()
+            .rev()
+            .find(|(lowest, _)| total >= *lowest)
+            .map(|(_, band)| *band)
+            .unwrap_or("unknown")
+    }
+
+    pub fn log_line(&self, responses: Vec<u8>, recorded_at: String) -> Result<LogLine, String> {
+        let total = self.score(&responses)?;
+        let answer = responses
+            .iter()
+            .map(u8::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(LogLine::Screening {
+            recorded_at,
+            instrument: self.name.to_string(),
+            responses,
+            total,
+            severity: self.severity(total).to_string(),
+            justification: String::new(),
+            provenance: vec![Provenance::local(self.name, answer, self.name, 0)],
+        })
+    }
+
+    /// The message to show for `responses`, if any item that warrants one was answered nonzero.
+    pub fn alert(&self, responses: &[u8]) -> Option<&'static str> {
+        let (item, message) = self.alert?;
+        match responses.get(item) {
+            Some(&response) if response > 0 => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Present every item with numbered choices and read one choice per item.  Returns None if
+    /// the user interrupts.
+    pub fn administer<H: Helper, I: History>(&self, editor: &mut Editor<H, I>) -> Option<Vec<u8>> {
+        println!("{}\n{}\n", self.name, self.stem);
+        for (score, choice) in self.choices.iter().enumerate() {
+            println!("  {score}) {choice}");
+        }
+        let last = self.choices.len() - 1;
+        let mut responses = vec![];
+        for (idx, item) in self.items.iter().enumerate() {
+            println!("\n{}. {item}", idx + 1);
+            loop {
+                let line = match editor.readline(&format!("[0-{last}]: ")) {
+                    Ok(line) => line,
+                    Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => return None,
+                    Err(err) => {
+                        eprintln!("could not read line: {}", err);
+                        std::process::exit(13);
+                    }
+                };
+                match line.trim().parse::<u8>() {
+                    Ok(response) if response as usize <= last => {
+                        responses.push(response);
+                        break;
+                    }
+                    _ => eprintln!("Please answer with a number from 0 to {last}."),
+                }
+            }
+        }
+        Some(responses)
+    }
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    arrrg_derive::CommandLine,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct ScreenOptions {
+    #[arrrg(
+        optional,
+        "Transcript to append to; defaults to $NOTAPSYCH_TRANSCRIPT."
+    )]
+    pub transcript: String,
+}
+
+pub fn main(args: &[String]) {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (options, free) = ScreenOptions::from_arguments_relaxed(
+        "USAGE: notapsych screen [--transcript FILE] phq-9|gad-7",
+        &args,
+    );
+    let [name] = free.as_slice() else {
+        eprintln!("USAGE: notapsych screen [--transcript FILE] phq-9|gad-7");
+        std::process::exit(13);
+    };
+    let Some(instrument) = Instrument::find(name) else {
+        eprintln!("unknown instrument {name:?}; expected phq-9 or gad-7");
+        std::process::exit(13);
+    };
+    let mut editor = crate::editor();
+    let Some(responses) = instrument.administer(&mut editor) else {
+        return;
+    };
+    let alert = instrument.alert(&responses);
+    let log_line = instrument
+        .log_line(responses, Local::now().fixed_offset().to_rfc3339())
+        .expect("administered responses should always score");
+    println!("\n{}", log_line.summary());
+    if let Some(alert) = alert {
+        println!("\n{alert}");
+    }
+    append_log_line(&options.transcript, &log_line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_sums_items() {
+        assert_eq!(Ok(0), PHQ_9.score(&[0; 9]));
+        assert_eq!(Ok(27), PHQ_9.score(&[3; 9]));
+        assert_eq!(Ok(6), GAD_7.score(&[0, 1, 2, 3, 0, 0, 0]));
+        assert_eq!(27, PHQ_9.max_total());
+        assert_eq!(21, GAD_7.max_total());
+    }
+
+    #[test]
+    fn score_rejects_bad_responses() {
+        assert!(PHQ_9.score(&[0; 7]).is_err());
+        assert!(GAD_7.score(&[0, 0, 0, 0, 0, 0, 4]).is_err());
+    }
+
+    #[test]
+    fn severity_bands() {
+        assert_eq!("minimal", PHQ_9.severity(0));
+        assert_eq!("minimal", PHQ_9.severity(4));
+        assert_eq!("mild", PHQ_9.severity(5));
+        assert_eq!("moderately severe", PHQ_9.severity(19));
+        assert_eq!("severe", PHQ_9.severity(27));
+        assert_eq!("moderate", GAD_7.severity(14));
+        assert_eq!("severe", GAD_7.severity(15));
+    }
+
+    #[test]
+    fn alert_on_item_nine() {
+        let mut responses = vec![0; 9];
+        assert_eq!(None, PHQ_9.alert(&responses));
+        responses[8] = 1;
+        assert!(PHQ_9.alert(&responses).is_some());
+        assert_eq!(None, GAD_7.alert(&[3; 7]));
+    }
+
+    #[test]
+    fn log_line_records_provenance() {
+        let log_line = GAD_7
+            .log_line(
+                vec![0, 1, 2, 3, 0, 0, 0],
+                "2025-06-10T08:00:00-07:00".to_string(),
+            )
+            .unwrap();
+        let [provenance] = log_line.provenance() else {
+            panic!("expected one provenance, got {:?}", log_line.provenance());
+        };
+        assert_eq!("GAD-7", provenance.question);
+        assert_eq!("0 1 2 3 0 0 0", provenance.answer);
+        assert_eq!(Provenance::LOCAL, provenance.backend);
+    }
+
+    #[test]
+    fn find_ignores_case_and_punctuation() {
+        assert_eq!(Some(PHQ_9), Instrument::find("phq9"));
+        assert_eq!(Some(GAD_7), Instrument::find("Gad-7"));
+        assert_eq!(None, Instrument::find("bdi"));
+    }
+}
@@ -1,25 +1,59 @@
-use std::fs::OpenOptions;
-use std::io::Write;
-
 use chrono::Local;
 use rustyline::config::EditMode;
 use rustyline::error::ReadlineError;
 use rustyline::hint::HistoryHinter;
 use rustyline::{CompletionType, Config, Editor, EventHandler, KeyEvent};
 
+pub mod alcohol;
+pub mod clock;
+pub mod eval;
+pub mod export;
+pub mod import;
+pub mod instrument;
+pub mod query;
+pub mod questionnaire;
+pub mod regimen;
+pub mod report;
+pub mod schedule;
+pub mod schema;
+pub mod sleep;
 pub mod stayfocused;
+pub mod transcript;
+pub mod units;
 
 mod cli;
 
+pub use questionnaire::{Question, Questionnaire};
+pub use transcript::{Dose, LogLine, Provenance, Reported, Review, Signal};
+
 use cli::{CommandHint, ShellHelper, TabEventHandler};
 
-const LAST_SLEPT: &str = "last-slept";
-const SLEPT_HOW_LONG: &str = "slept-how-long";
-const QUALITY_OF_SLEEP: &str = "quality-of-sleep";
-const MEDICATION: &str = "medication";
-const HYGIENE: &str = "hygiene";
+const DRINKING_EPISODE: &str = "drinking-episode";
+const JOURNAL: &str = "journal";
+
+const USAGE: &str =
+    "USAGE: notapsych checkin|journal|query|report|sleep|adherence|screen|schema|export|import [OPTIONS]";
+
+pub async fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    match args.get(1).map(String::as_str) {
+        Some("checkin") => notapsych(&args[2..]).await,
+        Some("journal") => journal().await,
+        Some("query") => query::main(&args[2..]),
+        Some("report") => report::main(&args[2..]),
+        Some("sleep") => sleep::main(&args[2..]),
+        Some("adherence") => regimen::main(&args[2..]),
+        Some("screen") => instrument::main(&args[2..]),
+        Some("schema") => schema::main(&args[2..]),
+        Some("export") => export::main(&args[2..]),
+        Some("import") => import::main(&args[2..]),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(13);
+        }
+    }
+}
 
-/*
 /////////////////////////////////////////////// Error //////////////////////////////////////////////
 
 #[derive(Debug)]
@@ -27,8 +61,6 @@ pub enum Error {
     Internal(String),
     IO(std::io::Error),
     Json(serde_json::Error),
-    Reqwest(reqwest::Error),
-    Yammer(yammer::Error),
 }
 
 impl std::fmt::Display for Error {
@@ -51,304 +83,398 @@ impl From<serde_json::Error> for Error {
     }
 }
 
-impl From<reqwest::Error> for Error {
-    fn from(err: reqwest::Error) -> Self {
-        Self::Reqwest(err)
-    }
+/// The backend `extract` sends answers to, as recorded in each line's provenance.
+const BACKEND: &str = "ollama";
+
+#[derive(serde::Deserialize)]
+struct MedicationAnswer {
+    substance: String,
+    quantity: f64,
+    units: String,
+    frequency: String,
+    times: f64,
+    justification: String,
 }
 
-impl From<yammer::Error> for Error {
-    fn from(err: yammer::Error) -> Self {
-        Self::Yammer(err)
+#[derive(serde::Deserialize)]
+struct HydrationAnswer {
+    quantity: f64,
+    units: String,
+    justification: String,
+}
+
+impl HydrationAnswer {
+    /// The water the user reported, converted to mL.
+    fn log_line(self, recorded_at: String, provenance: Vec<Provenance>) -> Result<LogLine, String> {
+        let Some(conversion) = units::conversion("water", &self.units) else {
+            return Err(format!("cannot convert {} of water to mL", self.units));
+        };
+        Ok(LogLine::Hydration {
+            recorded_at,
+            water_ml: self.quantity * conversion.factor,
+            justification: self.justification,
+            provenance,
+        })
     }
 }
 
+#[derive(serde::Deserialize)]
+struct DrinkingEpisodeAnswer {
+    started_hours_ago: f64,
+    duration_hours: f64,
+    justification: String,
+}
+
 ///////////////////////////////////////////// NotAPsych ////////////////////////////////////////////
 
 pub struct NotAPsych<HELPER: rustyline::Helper, HISTORY: rustyline::history::History> {
     editor: Editor<HELPER, HISTORY>,
+    questionnaire: Questionnaire,
 }
 
 impl<HELPER: rustyline::Helper, HISTORY: rustyline::history::History> NotAPsych<HELPER, HISTORY> {
-    pub async fn checkin(&mut self) {
-        self.sleep_checkin().await;
-        println!();
-        self.medications().await;
-        println!();
-        self.hygiene().await;
-    }
-
-    pub async fn sleep_checkin(&mut self) {
-        self.last_slept().await;
-        println!();
-        self.slept_how_long().await;
-        println!();
-        self.quality_of_sleep().await;
+    pub async fn checkin(&mut self, all: bool) {
+        let questions = if all {
+            self.questionnaire.checkin().cloned().collect::<Vec<_>>()
+        } else {
+            let answered = schedule::answered_from_env();
+            schedule::due(&self.questionnaire, &answered, Local::now().date_naive())
+                .into_iter()
+                .cloned()
+                .collect()
+        };
+        if questions.is_empty() {
+            println!("Nothing is due today; pass --all to check in anyway.");
+        }
+        for (idx, question) in questions.iter().enumerate() {
+            if idx > 0 {
+                println!();
+            }
+            self.ask(question).await;
+        }
     }
 
-    pub async fn last_slept(&mut self) {
-        #[derive(serde::Deserialize, yammer_derive::JsonSchema)]
-        struct LastSleptAnswer {
-            awake_hours: f64,
-            justification: String,
-        }
-        for idx in 0..3 {
-            let answer: LastSleptAnswer = match self
-                .question_and_answer(LAST_SLEPT, "When did you last wakeup? ")
-                .await
-            {
-                Ok(Some(answer)) => answer,
+    pub async fn ask(&mut self, question: &Question) {
+        let mut failures = 0;
+        while failures < 3 {
+            let (answer, provenance) = match self.question_and_answer(question).await {
+                Ok(Some(extracted)) => extracted,
+                Ok(None) if question.repeat_until_blank || question.optional => return,
                 Ok(None) => {
                     eprintln!("A blank answer is unacceptable (unless given three times).");
+                    failures += 1;
                     continue;
                 }
                 Err(err) => {
-                    if idx < 2 {
+                    failures += 1;
+                    if failures < 3 {
                         eprintln!("error: {err}\n\nPlease try again:\n\n");
                     }
                     continue;
                 }
             };
-            let log_line = LogLine::LastSlept {
-                recorded_at: Local::now().fixed_offset().to_rfc3339(),
-                awake_hours: answer.awake_hours,
-                justification: answer.justification,
-            };
-            self.log(log_line);
-            return;
+            if let Err(err) = self.record(question, answer, provenance).await {
+                failures += 1;
+                if failures < 3 {
+                    eprintln!("error: {err}\n\nPlease try again:\n\n");
+                }
+                continue;
+            }
+            if !question.repeat_until_blank {
+                return;
+            }
+            println!();
+            failures = 0;
         }
         eprintln!("Could not interpret input, moving on...\n\n");
     }
 
-    pub async fn slept_how_long(&mut self) {
-        #[derive(serde::Deserialize, yammer_derive::JsonSchema)]
-        struct SleptHowLongAnswer {
-            sleep_hours: f64,
-            justification: String,
+    async fn record(
+        &mut self,
+        question: &Question,
+        answer: serde_json::Value,
+        provenance: Provenance,
+    ) -> Result<(), Error> {
+        match question.log.as_str() {
+            "medication" => {
+                self.medication(serde_json::from_value(answer)?, provenance)
+                    .await
+            }
+            "hydration" => self.hydration(serde_json::from_value(answer)?, provenance)?,
+            _ => {
+                let recorded_at = Local::now().fixed_offset().to_rfc3339();
+                let log_line = question
+                    .log_line(&answer, recorded_at, vec![provenance])
+                    .map_err(Error::Internal)?;
+                self.log(log_line);
+            }
         }
-        for idx in 0..3 {
-            let answer: SleptHowLongAnswer = match self
-                .question_and_answer(
-                    SLEPT_HOW_LONG,
-                    "When you last slept, for how many hours did you sleep? ",
-                )
-                .await
-            {
-                Ok(Some(answer)) => answer,
-                Ok(None) => {
-                    eprintln!("A blank answer is unacceptable (unless given three times).");
-                    continue;
-                }
-                Err(err) => {
-                    if idx < 2 {
-                        eprintln!("error: {err}\n\nPlease try again:\n\n");
-                    }
-                    continue;
-                }
-            };
-            let log_line = LogLine::HoursSlept {
+        Ok(())
+    }
+
+    async fn medication(&mut self, answer: MedicationAnswer, provenance: Provenance) {
+        let dose = Dose::from_frequency(
+            &answer.frequency,
+            answer.quantity,
+            answer.units,
+            answer.times,
+        );
+        let (substance, dose, reported) = match units::normalize(&answer.substance, &dose) {
+            Some((substance, normalized)) => (
+                substance,
+                normalized,
+                Some(Reported {
+                    substance: answer.substance,
+                    dose,
+                }),
+            ),
+            None => (answer.substance, dose, None),
+        };
+        // Only a single sitting is an episode; a drinking habit is logged with its frequency.
+        let episode = matches!(dose, Dose::Once { .. });
+        if substance == "alcohol" && dose.units() == units::STANDARD_DRINKS && episode {
+            self.drinking_episode(
+                dose.total(),
+                reported,
+                answer.justification,
+                vec![provenance],
+            )
+            .await;
+        } else {
+            let log_line = LogLine::Medication {
                 recorded_at: Local::now().fixed_offset().to_rfc3339(),
-                sleep_hours: answer.sleep_hours,
+                substance,
+                dose,
+                reported,
                 justification: answer.justification,
+                provenance: vec![provenance],
             };
             self.log(log_line);
-            return;
         }
-        eprintln!("Could not interpret input, moving on...\n\n");
     }
 
-    pub async fn quality_of_sleep(&mut self) {
-        #[derive(serde::Deserialize, yammer_derive::JsonSchema)]
-        struct QualityOfSleepAnswer {
-            answer: f64,
-            justification: String,
-        }
-        for idx in 0..3 {
-            let answer: QualityOfSleepAnswer = match self
-                .question_and_answer(
-                    QUALITY_OF_SLEEP,
-                    "How would you rate the quality of your last sleep schedule on a scale of 1 being the worst and 10 being the best? "
-                )
-                .await
-            {
-                Ok(Some(answer)) => answer,
-                Ok(None) => {
-                    eprintln!("A blank answer is unacceptable (unless given three times).");
-                    continue;
+    fn hydration(&mut self, answer: HydrationAnswer, provenance: Provenance) -> Result<(), Error> {
+        let recorded_at = Local::now().fixed_offset().to_rfc3339();
+        let log_line = answer
+            .log_line(recorded_at, vec![provenance])
+            .map_err(Error::Internal)?;
+        self.log(log_line);
+        Ok(())
+    }
+
+    /// Read a multi-line entry, ended by a line holding only "." or by ^D, and log it with what
+    /// the model extracts from it.
+    pub async fn journal(&mut self) {
+        let Some(question) = self.questionnaire.get(JOURNAL).cloned() else {
+            eprintln!("the questionnaire has no {JOURNAL} question");
+            std::process::exit(13);
+        };
+        println!("{}", question.prompt);
+        let mut text = String::new();
+        loop {
+            match self.editor.readline("") {
+                Ok(line) if line.trim() == "." => break,
+                Ok(line) => {
+                    text += &line;
+                    text.push('\n');
                 }
+                Err(ReadlineError::Eof) => break,
+                Err(ReadlineError::Interrupted) => std::process::exit(0),
                 Err(err) => {
-                    if idx < 2 {
-                        eprintln!("error: {err}\n\nPlease try again:\n\n");
-                    }
-                    continue;
+                    eprintln!("could not read line: {}", err);
+                    std::process::exit(13);
                 }
-            };
-            let log_line = LogLine::SleepQuality {
-                recorded_at: Local::now().fixed_offset().to_rfc3339(),
-                answer: answer.answer,
-                justification: answer.justification,
-            };
-            self.log(log_line);
+            }
+        }
+        let text = text.trim().to_string();
+        if text.is_empty() {
             return;
         }
-        eprintln!("Could not interpret input, moving on...\n\n");
+        let recorded_at = Local::now().fixed_offset().to_rfc3339();
+        let log_line = match self.extract(&question, text.clone()).await {
+            Ok((mut answer, provenance)) => {
+                answer["text"] = text.clone().into();
+                question.log_line(&answer, recorded_at.clone(), vec![provenance])
+            }
+            Err(err) => Err(err.to_string()),
+        };
+        // The words matter more than the tags; never lose an entry because extraction failed.
+        let log_line = log_line.unwrap_or_else(|err| {
+            eprintln!("could not extract tags, saving the entry without them: {err}");
+            LogLine::Journal {
+                recorded_at,
+                text,
+                topics: vec![],
+                sentiment: None,
+                people: vec![],
+                signals: vec![],
+                justification: String::new(),
+                provenance: vec![],
+            }
+        });
+        self.log(log_line);
     }
 
-    pub async fn medications(&mut self) {
-        #[derive(serde::Deserialize, yammer_derive::JsonSchema)]
-        struct MedicationAnswer {
-            substance: String,
-            quantity: f64,
-            units: String,
-            times_daily: f64,
-            justification: String,
-        }
-        let mut failures = 0;
-        loop {
-            let answer: MedicationAnswer = match self.question_and_answer(MEDICATION,
-                    "List every medication you took since last checkin, and the dosage, e.g. \"30mg magic pill 3x daily.\"
-List caffeine, nicotine, and other substances as appropriate.
-Enter an empty line to continue: ").await {
-                Ok(Some(answer)) => answer,
-                Ok(None) => {
-                    return;
+    /// Log an episode of `standard_drinks`, asking when it started and how long it lasted.
+    /// `reported` is what the user said before it was converted to standard drinks.
+    /// `provenance` describes how the drinks were extracted; the follow-up's is added to it.
+    pub async fn drinking_episode(
+        &mut self,
+        standard_drinks: f64,
+        reported: Option<Reported>,
+        justification: String,
+        mut provenance: Vec<Provenance>,
+    ) {
+        let now = Local::now().fixed_offset();
+        let answer = match self.questionnaire.get(DRINKING_EPISODE).cloned() {
+            Some(question) => match self.question_and_answer(&question).await {
+                Ok(Some((answer, followup))) => {
+                    provenance.push(followup);
+                    serde_json::from_value::<DrinkingEpisodeAnswer>(answer).ok()
                 }
-                Err(err) => {
-                    if failures < 2 {
-                        eprintln!("error: {err}\n\nPlease try again:\n\n");
-                        continue;
-                    } else {
-                        eprintln!("Could not interpret input, moving on...\n\n");
-                        return;
+                Ok(None) | Err(_) => None,
+            },
+            None => None,
+        };
+        let (started_at, duration_hours, justification) = match answer {
+            Some(answer) => (
+                now - chrono::Duration::minutes((answer.started_hours_ago * 60.0) as i64),
+                answer.duration_hours,
+                justification + " " + &answer.justification,
+            ),
+            None => (
+                now,
+                0.0,
+                justification + " No start time or duration given; assuming all at once.",
+            ),
+        };
+        let log_line = LogLine::Alcohol {
+            recorded_at: now.to_rfc3339(),
+            standard_drinks,
+            started_at: started_at.to_rfc3339(),
+            duration_hours,
+            reported,
+            justification,
+            provenance,
+        };
+        self.log(log_line);
+    }
+
+    /// Ask `question`, extract an answer, and have the user confirm or correct it.  Answering
+    /// again starts over.
+    async fn question_and_answer(
+        &mut self,
+        question: &Question,
+    ) -> Result<Option<(serde_json::Value, Provenance)>, Error> {
+        loop {
+            let answer = self.read_line(&question.prompt).await;
+            if answer.trim().is_empty() {
+                return Ok(None);
+            }
+            let started = std::time::Instant::now();
+            let (parsed, provenance) =
+                match question.parse_locally(&answer, Local::now().fixed_offset()) {
+                    Some(parsed) => {
+                        let parser = question
+                            .parse
+                            .as_ref()
+                            .map(|parser| parser.name())
+                            .unwrap_or_default();
+                        let provenance = Provenance::local(
+                            &question.slug,
+                            answer,
+                            parser,
+                            started.elapsed().as_millis() as u64,
+                        );
+                        (parsed, provenance)
                     }
-                }
-            };
-            let log_line = LogLine::Medication {
-                recorded_at: Local::now().fixed_offset().to_rfc3339(),
-                substance: answer.substance,
-                dose: Dose::Daily {
-                    quantity: answer.quantity,
-                    units: answer.units,
-                    times_daily: answer.times_daily,
-                },
-                justification: answer.justification,
-            };
-            self.log(log_line);
-            println!();
-            failures = 0;
+                    None => self.extract(question, answer).await?,
+                };
+            if let Some(reviewed) = self.review(question, parsed, provenance).await {
+                return Ok(Some(reviewed));
+            }
         }
     }
 
-    pub async fn hygiene(&mut self) {
-        let hygiene_system = self.load_system(HYGIENE);
-        let hygiene = self
+    /// Show the values extracted for `question` and let the user accept them, correct a field
+    /// in place, or answer again (None).
+    async fn review(
+        &mut self,
+        question: &Question,
+        extracted: serde_json::Value,
+        provenance: Provenance,
+    ) -> Option<(serde_json::Value, Provenance)> {
+        let mut answer = extracted.clone();
+        loop {
+            print!("{}", question.describe(&answer));
+            let choice = self
                 .read_line(
-                    "Describe your hygiene in a way that translates to POOR, FAIR, GOOD, GREAT, EXCELLENT since your last report: "
+                    "Enter to accept, a field name to correct it, or \"r\" to answer again: ",
                 )
                 .await;
-        let req = GenerateRequest {
-            model: self.model(),
-            prompt: hygiene,
-            format: Some(serde_json::json! {{
-                "type": "object",
-                "properties": {
-                    "answer": {
-                        "type": "string",
-                        "enum": [
-                            "POOR",
-                            "FAIR",
-                            "GOOD",
-                            "GREAT",
-                            "EXCELLENT"
-                        ]
-                    },
-                    "justification": {
-                        "type": "string"
-                    }
-                },
-                "required": [
-                  "answer",
-                  "justification"
-                ]
-            }}),
-            system: Some(hygiene_system),
-            suffix: None,
-            stream: Some(false),
-            images: None,
-            template: None,
-            raw: None,
-            keep_alive: None,
-            options: None,
-        };
-        let req = req.make_request(&self.ollama_host());
-        let spinner = Spinner::new();
-        spinner.start();
-        let resp = req
-            .send()
-            .await
-            .expect("encountered an error")
-            .error_for_status()
-            .expect("encountered an error")
-            .text()
-            .await
-            .expect("encountered an error");
-        let resp: yammer::GenerateResponse =
-            serde_json::from_str(&resp).expect("json should parse");
-        spinner.inhibit();
-        #[derive(serde::Deserialize)]
-        struct Justification {
-            answer: String,
-            justification: String,
-        }
-        let answer: Justification = match serde_json::from_str(&resp.response) {
-            Ok(json) => json,
-            Err(err) => {
-                eprintln!("Model gave bogus json: {err}");
-                return;
+            let field = match choice.trim() {
+                "" | "y" | "yes" => break,
+                "r" => return None,
+                field => field.to_string(),
+            };
+            let Some(current) = answer.get(&field) else {
+                eprintln!("There is no {field} to correct.");
+                continue;
+            };
+            let current = match current {
+                serde_json::Value::String(s) => s.clone(),
+                value => value.to_string(),
+            };
+            let text = match self
+                .editor
+                .readline_with_initial(&format!("{field}: "), (&current, ""))
+            {
+                Ok(text) => text,
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => continue,
+                Err(err) => {
+                    eprintln!("could not read line: {}", err);
+                    std::process::exit(13);
+                }
+            };
+            match question.edit(&answer, &field, &text) {
+                Ok(edited) => answer = edited,
+                Err(err) => eprintln!("{err}"),
             }
-        };
-        let log_line = LogLine::Hygiene {
-            recorded_at: Local::now().fixed_offset().to_rfc3339(),
-            hygiene: answer.answer,
-            justification: answer.justification,
-        };
-        self.log(log_line);
+        }
+        let provenance = provenance.reviewed(extracted, &answer);
+        Some((answer, provenance))
     }
 
-    async fn question_and_answer<T: for<'a> serde::Deserialize<'a> + yammer::JsonSchema>(
+    async fn extract(
         &mut self,
-        system: &str,
-        question: &str,
-    ) -> Result<Option<T>, Error> {
-        let system = self.load_system(system);
-        let answer = self.read_line(question).await;
-        if answer.trim().is_empty() {
-            return Ok(None);
-        }
-        let req = GenerateRequest {
-            model: self.model(),
-            prompt: answer,
-            format: Some(T::json_schema()),
-            system: Some(system),
-            suffix: None,
-            stream: Some(false),
-            images: None,
-            template: None,
-            raw: None,
-            keep_alive: None,
-            options: None,
+        question: &Question,
+        answer: String,
+    ) -> Result<(serde_json::Value, Provenance), Error> {
+        let model = self.model();
+        let started = std::time::Instant::now();
+        let resp = question
+            .generate(
+                &self.ollama_host(),
+                &model,
+                &answer,
+                Local::now().fixed_offset(),
+            )
+            .await
+            .map_err(Error::Internal)?;
+        let latency = started.elapsed();
+        let provenance = Provenance {
+            question: question.slug.clone(),
+            answer,
+            model,
+            backend: BACKEND.to_string(),
+            prompt: question.prompt_hash(),
+            latency_ms: latency.as_millis() as u64,
+            review: None,
+            extracted: None,
         };
-        let req = req.make_request(&self.ollama_host());
-        let spinner = Spinner::new();
-        spinner.start();
-        let resp = req
-            .send()
-            .await?
-            .error_for_status()?
-            .json::<GenerateResponse>()
-            .await?;
-        spinner.inhibit();
-        Ok(Some(serde_json::from_str(&resp.response)?))
+        let answer = serde_json::from_str(&resp)?;
+        question.validate(&answer).map_err(Error::Internal)?;
+        Ok((answer, provenance))
     }
 
     fn model(&self) -> String {
@@ -361,71 +487,6 @@ Enter an empty line to continue: ").await {
         }
     }
 
-    fn load_system(&self, slug: &str) -> String {
-        // TODO(rescrv):  Load from filesystem or a remote database?
-        match slug {
-            LAST_SLEPT => r#"Measure the time since the user reports they last wokeup.
-
-You are to provide your answer in hours, along with a justification in plain text.  Respond in
-JSON.
-
-To calculate this accurately, you must think step-by-step.  For example, if the user reports they
-last slept at 5:30am yesterday, and it is now 3:15pm today, first compute that there are 18.5 hours
-between 5:30am and midnight, and then 15.25 hours between midnight and now.  Add 18.5 + 15.25 to
-get 33.75 hours.  Double check your math by working in reverse, starting from now and computing backwards,
-
-Triple check your results by computing the roundup to the nearest hour at each end and then count
-the intervening hours.  For example, if the user reports they last woke at 7:25am and it is now
-5:45pm., round up 25 minutes to the hour to get 35 minutes, round 5:45pm down to the hour to get 45
-minutes (the number of minutes past the hour).  Then count that there are 9 hours between 8:00am
-and 5:00pm, for a total of 9 hours + 35 minutes + 45 minutes, or 10 hours, 20 minutes.
-
-When all three computations agree, report your results.
-
-"#
-            .to_string() + &format!("It is currently {}.", Local::now().to_rfc2822()),
-            SLEPT_HOW_LONG => r#"Measure the number of hours the user reports they slept during their most recent sleep cycle.
-
-Example:
-"8 hours" => {"sleep_hours": 8, "justification": "The user said they slept 8 hours."}
-"#.to_string(),
-            QUALITY_OF_SLEEP => r#"Interpret the user's response as a number on a scale from 0.0 to 10.0"#.to_string(),
-            MEDICATION => r#"Parse the amount of medication the user reports taking.
-
-Report -1 times-daily when there is not enough information to make a decision.
-
-Respond using JSON.
-
-Example generic:
-30mg of something once daily => {"medication": "something", "quantity": 30, "units": "mg", "times-daily": 1}
-
-Example branded, as needed (report once per day):
-Advil, 200mg, as needed => {"medication": "Advil", "quantity": 200, "units": "mg", "times-daily": 1}
-
-Example caffeine:
-Two cups of coffee per day => {"medication": "caffeine", "quantity": 150, "units": "mg", "times-daily": 2}
-
-Example alcohol (assume: 0.02 BAC per liquor shot, wine glass, or beer bottle, in a single sitting):
-Two shots of whisky and a shot of whiskey => {"medication": "alcohol", "quantity": 0.06, "units": "BAC", "times-daily": 1}
-One glass of red wine with dinner => {"medication": "alcohol", "quantity": 0.02, "units": "BAC", "times-daily": 1}
-A fifth of Jack, once a month => {"medication": "alcohol", "quantity": 0.48, "units": "BAC", "times-daily": 0.033333}
-A fifth of Jack => {"medication": "alcohol", "quantity": 0.48, "units": "BAC", "times-daily": -1}
-A thirty rack of bud with Tucker => {"medication": "alcohol", "quantity": 0.60, "units": "BAC", "times-daily": 1}
-
-Example nicotine:
-Smoke a pack a day => {"medication": "nicotine", "quantity": 10, "units": "mg", "times-daily": 20}
-Smoke two packs a day => {"medication": "nicotine", "quantity": 10, "units": "mg", "times-daily": 40}
-"#.to_string(),
-            HYGIENE => r#"Make a judgement call about the user's hygiene habits.
-
-Someone who showers and shaves every day has excellent hygiene.
-Someone who showers infrequently has poor hygiene.
-It is a spectrum of POOR, FAIR, GOOD, GREAT, EXCELLENT.
-"#.to_string(),
-            _ => panic!("logic error: {slug} not supported"),
-        }
-    }
-
     fn ollama_host(&self) -> String {
         match std::env::var("OLLAMA_HOST") {
             Ok(model) => model,
@@ -437,23 +498,7 @@ It is a spectrum of POOR, FAIR, GOOD, GREAT, EXCELLENT.
     }
 
     fn log(&self, log_line: LogLine) {
-        let transcript = match std::env::var("NOTAPSYCH_TRANSCRIPT") {
-            Ok(transcript) => transcript,
-            Err(_) => {
-                eprintln!("please set NOTAPSYCH_TRANSCRIPT in your environment");
-                std::process::exit(13);
-            }
-        };
-        let mut log = OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(transcript)
-            .expect("could not open transcript for append");
-        log.write_all(
-            (serde_json::to_string(&log_line).expect("log line should always serialize") + "\n")
-                .as_bytes(),
-        )
-        .expect("could not append to log; it may be corrupt");
+        query::append_log_line("", &log_line);
     }
 
     async fn read_line(&mut self, question: &str) -> String {
@@ -470,7 +515,8 @@ It is a spectrum of POOR, FAIR, GOOD, GREAT, EXCELLENT.
     }
 }
 
-pub async fn notapsych() {
+/// The line editor every interactive command shares, so edit mode and history behave the same.
+pub(crate) fn editor() -> Editor<ShellHelper, rustyline::history::FileHistory> {
     let config = Config::builder()
         .auto_add_history(true)
         .edit_mode(EditMode::Vi)
@@ -499,54 +545,67 @@ pub async fn notapsych() {
         KeyEvent::from('\t'),
         EventHandler::Conditional(Box::new(TabEventHandler)),
     );
-    let mut not_a_psych = NotAPsych { editor: rl };
-    not_a_psych.checkin().await;
+    rl
 }
 
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
-#[serde(tag = "type")]
-pub enum LogLine {
-    #[serde(rename = "last-slept")]
-    LastSlept {
-        recorded_at: String,
-        awake_hours: f64,
-        justification: String,
-    },
-    #[serde(rename = "hours-slept")]
-    HoursSlept {
-        recorded_at: String,
-        sleep_hours: f64,
-        justification: String,
-    },
-    #[serde(rename = "sleep-quality")]
-    SleepQuality {
-        recorded_at: String,
-        answer: f64,
-        justification: String,
-    },
-    #[serde(rename = "medication")]
-    Medication {
-        recorded_at: String,
-        substance: String,
-        dose: Dose,
-        justification: String,
-    },
-    #[serde(rename = "hygiene")]
-    Hygiene {
-        recorded_at: String,
-        hygiene: String,
-        justification: String,
-    },
+pub async fn notapsych(args: &[String]) {
+    use arrrg::CommandLine;
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (options, free) =
+        schedule::CheckinOptions::from_arguments_relaxed("USAGE: notapsych checkin [--all]", &args);
+    if !free.is_empty() {
+        eprintln!("command takes no positional arguments");
+        std::process::exit(13);
+    }
+    let mut not_a_psych = NotAPsych {
+        editor: editor(),
+        questionnaire: Questionnaire::from_env(),
+    };
+    not_a_psych.checkin(options.all).await;
+}
+
+pub async fn journal() {
+    let mut not_a_psych = NotAPsych {
+        editor: editor(),
+        questionnaire: Questionnaire::from_env(),
+    };
+    not_a_psych.journal().await;
 }
 
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
-#[serde(tag = "type")]
-pub enum Dose {
-    #[serde(rename = "daily")]
-    Daily {
-        quantity: f64,
-        units: String,
-        times_daily: f64,
-    },
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hydration(quantity: f64, units: &str) -> Result<f64, String> {
+        let answer = HydrationAnswer {
+            quantity,
+            units: units.to_string(),
+            justification: String::new(),
+        };
+        match answer.log_line(String::new(), vec![])? {
+            LogLine::Hydration { water_ml, .. } => Ok(water_ml),
+            log_line => Err(format!("unexpected {}", log_line.kind())),
+        }
+    }
+
+    #[test]
+    fn hydration_converts_to_ml() {
+        assert_eq!(Ok(1500.0), hydration(1.5, "L"));
+        assert_eq!(Ok(250.0), hydration(250.0, "mL"));
+        assert_eq!(Ok(4.0 * units::ML_PER_CUP), hydration(4.0, "cup"));
+        assert!(hydration(2.0, "bottle").unwrap() > 0.0);
+        assert!(hydration(1.0, "bucket").is_err());
+    }
+
+    #[test]
+    fn checkin_asks_the_daily_sections() {
+        let questionnaire = Questionnaire::builtin();
+        let slugs = questionnaire
+            .checkin()
+            .map(|q| q.slug.as_str())
+            .collect::<Vec<_>>();
+        for slug in ["mood", "exercise", "meals", "hydration"] {
+            assert!(slugs.contains(&slug), "{slug} is not asked");
+        }
+    }
 }
-*/
@@ -0,0 +1,170 @@
+use arrrg::CommandLine;
+use chrono::NaiveDate;
+
+use crate::transcript::{self, Entry, Filter, LogLine, Transcript, Versioned};
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    arrrg_derive::CommandLine,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct QueryOptions {
+    #[arrrg(optional, "Transcript to read; defaults to $NOTAPSYCH_TRANSCRIPT.")]
+    pub transcript: String,
+    #[arrrg(
+        optional,
+        "Comma-separated entry types to keep, e.g. hours-slept,medication."
+    )]
+    pub kind: String,
+    #[arrrg(optional, "Keep entries recorded on or after this day (YYYY-MM-DD).")]
+    pub since: String,
+    #[arrrg(optional, "Keep entries recorded on or before this day (YYYY-MM-DD).")]
+    pub until: String,
+    #[arrrg(optional, "Keep medication entries for this substance.")]
+    pub substance: String,
+    #[arrrg(optional, "Keep entries extracted with the prompt of this hash.")]
+    pub prompt: String,
+    #[arrrg(optional, "Output format: table or json.")]
+    pub format: String,
+}
+
+impl QueryOptions {
+    pub fn filter(&self) -> Filter {
+        Filter {
+            kinds: self
+                .kind
+                .split(',')
+                .map(str::trim)
+                .filter(|kind| !kind.is_empty())
+                .map(String::from)
+                .collect(),
+            since: parse_day("--since", &self.since),
+            until: parse_day("--until", &self.until),
+            substance: if self.substance.is_empty() {
+                None
+            } else {
+                Some(self.substance.clone())
+            },
+            prompt: if self.prompt.is_empty() {
+                None
+            } else {
+                Some(self.prompt.clone())
+            },
+        }
+    }
+}
+
+/// Parse an optional YYYY-MM-DD command-line value, exiting on garbage.
+pub(crate) fn parse_day(flag: &str, day: &str) -> Option<NaiveDate> {
+    if day.is_empty() {
+        return None;
+    }
+    match NaiveDate::parse_from_str(day, "%Y-%m-%d") {
+        Ok(day) => Some(day),
+        Err(err) => {
+            eprintln!("{flag} should be YYYY-MM-DD: {err}");
+            std::process::exit(13);
+        }
+    }
+}
+
+/// Open the transcript at `path`, or at `NOTAPSYCH_TRANSCRIPT` when `path` is empty, exiting
+/// when neither can be read.
+pub(crate) fn open_transcript(path: &str) -> Transcript<std::io::BufReader<std::fs::File>> {
+    let transcript = if path.is_empty() {
+        Transcript::from_env()
+    } else {
+        Transcript::open(path)
+    };
+    transcript.unwrap_or_else(|err| {
+        eprintln!("could not open transcript: {err}");
+        std::process::exit(13);
+    })
+}
+
+/// The transcript to write to:  `path`, or `NOTAPSYCH_TRANSCRIPT` when `path` is empty.
+pub(crate) fn transcript_path(path: &str) -> String {
+    if !path.is_empty() {
+        return path.to_string();
+    }
+    match std::env::var("NOTAPSYCH_TRANSCRIPT") {
+        Ok(transcript) => transcript,
+        Err(_) => {
+            eprintln!("please set NOTAPSYCH_TRANSCRIPT in your environment");
+            std::process::exit(13);
+        }
+    }
+}
+
+/// Append `log_line` to the transcript at `path` (see [transcript_path]), exiting on failure.
+pub(crate) fn append_log_line(path: &str, log_line: &LogLine) {
+    let path = transcript_path(path);
+    if let Err(err) = transcript::append(&path, log_line) {
+        eprintln!("could not append to {path}; it may be corrupt: {err}");
+        std::process::exit(13);
+    }
+}
+
+/// Read every parseable log line from the transcript, warning about the ones that were skipped.
+pub(crate) fn read_log_lines(path: &str) -> Vec<LogLine> {
+    let mut log_lines = vec![];
+    for entry in open_transcript(path) {
+        match entry {
+            Entry::LogLine(log_line) => log_lines.push(log_line),
+            Entry::Unknown { line, kind } => {
+                eprintln!("line {line}: skipping unknown entry type {kind:?}");
+            }
+            Entry::Malformed { line, error } => {
+                eprintln!("line {line}: skipping malformed entry: {error}");
+            }
+        }
+    }
+    log_lines
+}
+
+pub fn main(args: &[String]) {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (options, free) = QueryOptions::from_arguments_relaxed(
+        "USAGE: notapsych query [--kind K1,K2] [--since DAY] [--until DAY] [--substance S] [--prompt HASH] [--format table|json]",
+        &args,
+    );
+    if !free.is_empty() {
+        eprintln!("command takes no positional arguments");
+        std::process::exit(13);
+    }
+    let filter = options.filter();
+    let log_lines = read_log_lines(&options.transcript)
+        .into_iter()
+        .filter(|log_line| filter.matches(log_line));
+    match options.format.as_str() {
+        "" | "table" => {
+            for log_line in log_lines {
+                println!(
+                    "{:<25}  {:<13}  {:<28}  {}",
+                    log_line.recorded_at(),
+                    log_line.kind(),
+                    log_line.summary(),
+                    log_line.justification(),
+                );
+            }
+        }
+        "json" => {
+            for log_line in log_lines {
+                println!(
+                    "{}",
+                    serde_json::to_string(&Versioned::new(&log_line))
+                        .expect("log line should always serialize")
+                );
+            }
+        }
+        format => {
+            eprintln!("unknown format {format:?}; expected table or json");
+            std::process::exit(13);
+        }
+    }
+}
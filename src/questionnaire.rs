@@ -0,0 +1,458 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, FixedOffset, Local};
+
+use crate::clock::Parser;
+use crate::schedule::Cadence;
+use crate::transcript::{LogLine, Provenance};
+
+/// The questionnaire `notapsych checkin` asks when `NOTAPSYCH_QUESTIONS` is not set.
+pub const BUILTIN: &str = include_str!("../questions/checkin.toml");
+
+/////////////////////////////////////////////// Range //////////////////////////////////////////////
+
+/// Inclusive bounds on a numeric answer.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Range {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+impl Range {
+    pub fn contains(&self, value: f64) -> bool {
+        self.min.map(|min| value >= min).unwrap_or(true)
+            && self.max.map(|max| value <= max).unwrap_or(true)
+    }
+}
+
+////////////////////////////////////////////// Question ////////////////////////////////////////////
+
+/// One question, as written in a questionnaire file.  See `questions/checkin.toml` for what each
+/// field means.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Question {
+    pub slug: String,
+    pub prompt: String,
+    pub system: String,
+    #[serde(default)]
+    pub now: bool,
+    pub schema: serde_json::Value,
+    #[serde(default)]
+    pub ranges: BTreeMap<String, Range>,
+    #[serde(default)]
+    pub repeat_until_blank: bool,
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(default)]
+    pub followup: bool,
+    #[serde(default)]
+    pub cadence: Cadence,
+    pub log: String,
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parse: Option<Parser>,
+}
+
+impl Question {
+    /// The system prompt to send with this question.
+    pub fn system_prompt(&self) -> String {
+        self.system_prompt_at(Local::now().fixed_offset())
+    }
+
+    /// The system prompt to send with this question were it asked at `now`.
+    pub fn system_prompt_at(&self, now: DateTime<FixedOffset>) -> String {
+        if self.now {
+            format!("{}It is currently {}.", self.system, now.to_rfc2822())
+        } else {
+            self.system.clone()
+        }
+    }
+
+    /// A short, stable hash of the system prompt, before the current time is appended, that
+    /// tells one version of a prompt from another.
+    pub fn prompt_hash(&self) -> String {
+        format!("{:08x}", fnv1a(&self.system) >> 32)
+    }
+
+    /// Ask the ollama at `host` to have `model` answer this question from what the user said, as
+    /// asked at `now`, returning the model's raw response.
+    pub async fn generate(
+        &self,
+        host: &str,
+        model: &str,
+        answer: &str,
+        now: DateTime<FixedOffset>,
+    ) -> Result<String, String> {
+        let req = serde_json::json! {{
+            "model": model,
+            "prompt": answer,
+            "system": self.system_prompt_at(now),
+            "format": self.schema,
+            "stream": false,
+        }};
+        let host = if host.contains("://") {
+            host.to_string()
+        } else {
+            format!("http://{host}")
+        };
+        let resp = reqwest::Client::new()
+            .post(format!("{}/api/generate", host.trim_end_matches('/')))
+            .header("content-type", "application/json")
+            .body(req.to_string())
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| err.to_string())?
+            .text()
+            .await
+            .map_err(|err| err.to_string())?;
+        let resp: serde_json::Value = serde_json::from_str(&resp).map_err(|err| err.to_string())?;
+        resp.get("response")
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| "ollama returned no response".to_string())
+    }
+
+    /// Check that `answer` has every field the schema requires, that enumerated fields take one
+    /// of their listed values, and that numeric fields fall within their ranges.  Neither the
+    /// model nor a user correcting it always honors the schema, so answers are checked before
+    /// they are logged.
+    pub fn validate(&self, answer: &serde_json::Value) -> Result<(), String> {
+        let Some(object) = answer.as_object() else {
+            return Err(format!("{}: answer is not an object", self.slug));
+        };
+        let required = self
+            .schema
+            .get("required")
+            .and_then(serde_json::Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+        for field in required.iter().filter_map(serde_json::Value::as_str) {
+            if !object.contains_key(field) {
+                return Err(format!("{}: answer is missing {field}", self.slug));
+            }
+        }
+        let properties = self
+            .schema
+            .get("properties")
+            .and_then(serde_json::Value::as_object);
+        for (field, property) in properties.into_iter().flatten() {
+            let (Some(value), Some(allowed)) = (
+                object.get(field),
+                property.get("enum").and_then(serde_json::Value::as_array),
+            ) else {
+                continue;
+            };
+            if !allowed.contains(value) {
+                return Err(format!(
+                    "{}: {field}={value} is not one of {}",
+                    self.slug,
+                    allowed
+                        .iter()
+                        .map(serde_json::Value::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+        for (field, range) in self.ranges.iter() {
+            let Some(value) = object.get(field) else {
+                continue;
+            };
+            let Some(value) = value.as_f64() else {
+                return Err(format!("{}: {field} is not a number", self.slug));
+            };
+            if !range.contains(value) {
+                return Err(format!(
+                    "{}: {field}={value} is outside {}..={}",
+                    self.slug,
+                    range.min.map(|x| x.to_string()).unwrap_or_default(),
+                    range.max.map(|x| x.to_string()).unwrap_or_default(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer from `answer` without the model, when this question has a parser and the parser
+    /// understands the answer.  The parsed answer is validated like the model's would be.
+    pub fn parse_locally(
+        &self,
+        answer: &str,
+        now: DateTime<FixedOffset>,
+    ) -> Option<serde_json::Value> {
+        let parser = self.parse.as_ref()?;
+        let (hours, justification) = parser.parse(answer, now)?;
+        let mut parsed = serde_json::Map::new();
+        parsed.insert(parser.field().to_string(), hours.into());
+        parsed.insert("justification".to_string(), justification.into());
+        let parsed = serde_json::Value::Object(parsed);
+        self.validate(&parsed).ok()?;
+        Some(parsed)
+    }
+
+    /// Render an answer field by field for the user to look over, justification last.
+    pub fn describe(&self, answer: &serde_json::Value) -> String {
+        let mut fields = answer.as_object().into_iter().flatten().collect::<Vec<_>>();
+        fields.sort_by_key(|(field, _)| *field == "justification");
+        let mut out = String::new();
+        for (field, value) in fields {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                value => value.to_string(),
+            };
+            out += &format!("  {field}: {value}\n");
+        }
+        out
+    }
+
+    /// Replace `field` of `answer` with what the user typed, read as the same type as the value
+    /// it replaces, and check the result as if the model had answered it.
+    pub fn edit(
+        &self,
+        answer: &serde_json::Value,
+        field: &str,
+        text: &str,
+    ) -> Result<serde_json::Value, String> {
+        let Some(current) = answer.get(field) else {
+            return Err(format!("{}: there is no field {field}", self.slug));
+        };
+        let value = match current {
+            serde_json::Value::String(_) => text.trim().into(),
+            _ => serde_json::from_str(text.trim())
+                .map_err(|err| format!("{}: {field}: cannot read {text:?}: {err}", self.slug))?,
+        };
+        let mut edited = answer.clone();
+        edited[field] = value;
+        self.validate(&edited)?;
+        Ok(edited)
+    }
+
+    /// Turn a validated answer into the log line named by `log`, renaming fields per `fields`,
+    /// noting how it was extracted in `provenance`.
+    /// This works for log lines whose fields come straight from the answer; those that derive
+    /// fields from the answer (medication doses, drinking episodes, water intake) are built by the
+    /// check-in.
+    pub fn log_line(
+        &self,
+        answer: &serde_json::Value,
+        recorded_at: String,
+        provenance: Vec<Provenance>,
+    ) -> Result<LogLine, String> {
+        self.validate(answer)?;
+        let mut object = serde_json::Map::new();
+        for (field, value) in answer.as_object().into_iter().flatten() {
+            let field = self.fields.get(field).unwrap_or(field);
+            object.insert(field.clone(), value.clone());
+        }
+        object.insert("type".to_string(), self.log.clone().into());
+        object.insert("recorded_at".to_string(), recorded_at.into());
+        object.insert(
+            "provenance".to_string(),
+            serde_json::to_value(provenance).expect("provenance should always serialize"),
+        );
+        serde_json::from_value(serde_json::Value::Object(object))
+            .map_err(|err| format!("{}: cannot log as {}: {err}", self.slug, self.log))
+    }
+}
+
+/// FNV-1a of `s`.  Unlike std's hashers it is the same on every machine and every build, so it
+/// can name a prompt in the transcript or seed a roll that must not change between runs.
+pub(crate) fn fnv1a(s: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in s.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/////////////////////////////////////////// Questionnaire //////////////////////////////////////////
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Questionnaire {
+    pub questions: Vec<Question>,
+}
+
+impl Questionnaire {
+    pub fn parse(questionnaire: &str) -> Result<Self, String> {
+        let questionnaire: Self = toml::from_str(questionnaire).map_err(|err| err.to_string())?;
+        for question in questionnaire.questions.iter() {
+            if !LogLine::KINDS.contains(&question.log.as_str()) {
+                return Err(format!(
+                    "{}: unknown log type {:?}; expected one of {}",
+                    question.slug,
+                    question.log,
+                    LogLine::KINDS.join(", ")
+                ));
+            }
+        }
+        Ok(questionnaire)
+    }
+
+    pub fn load(path: &str) -> Result<Self, std::io::Error> {
+        let questionnaire = std::fs::read_to_string(path)?;
+        Self::parse(&questionnaire)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN).expect("builtin questionnaire should always parse")
+    }
+
+    /// Load the questionnaire at `NOTAPSYCH_QUESTIONS`, or the builtin one when it is unset.
+    pub fn from_env() -> Self {
+        let Ok(path) = std::env::var("NOTAPSYCH_QUESTIONS") else {
+            return Self::builtin();
+        };
+        match Self::load(&path) {
+            Ok(questionnaire) => questionnaire,
+            Err(err) => {
+                eprintln!("could not load questions from {path}: {err}");
+                std::process::exit(13);
+            }
+        }
+    }
+
+    pub fn get(&self, slug: &str) -> Option<&Question> {
+        self.questions.iter().find(|q| q.slug == slug)
+    }
+
+    /// The questions a check-in walks through, in order.
+    pub fn checkin(&self) -> impl Iterator<Item = &Question> {
+        self.questions.iter().filter(|q| !q.followup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(slug: &str, answer: serde_json::Value) -> Result<LogLine, String> {
+        let questionnaire = Questionnaire::builtin();
+        let question = questionnaire.get(slug).unwrap();
+        question.log_line(&answer, "2025-06-10T08:00:00-07:00".to_string(), vec![])
+    }
+
+    #[test]
+    fn log_mood() {
+        let answer = serde_json::json!({
+            "valence": 2, "arousal": 3, "anxiety": 6, "irritability": 1,
+            "emotions": ["tired"], "justification": "",
+        });
+        match log("mood", answer).unwrap() {
+            LogLine::Mood {
+                valence,
+                anxiety,
+                emotions,
+                ..
+            } => {
+                assert_eq!(2.0, valence);
+                assert_eq!(6.0, anxiety);
+                assert_eq!(vec!["tired".to_string()], emotions);
+            }
+            log_line => panic!("expected mood, got {}", log_line.kind()),
+        }
+        let answer = serde_json::json!({
+            "valence": 9, "arousal": 3, "anxiety": 6, "irritability": 1,
+            "emotions": [], "justification": "",
+        });
+        assert!(log("mood", answer).is_err());
+    }
+
+    #[test]
+    fn log_exercise() {
+        let answer = serde_json::json!({
+            "activity": "running", "duration_minutes": 30, "intensity": "vigorous",
+            "justification": "",
+        });
+        match log("exercise", answer).unwrap() {
+            LogLine::Exercise {
+                duration_minutes, ..
+            } => assert_eq!(30.0, duration_minutes),
+            log_line => panic!("expected exercise, got {}", log_line.kind()),
+        }
+    }
+
+    #[test]
+    fn log_meals() {
+        let answer = serde_json::json!({
+            "meals": 2, "skipped": 1, "regular": false, "justification": "",
+        });
+        match log("meals", answer).unwrap() {
+            LogLine::Meals {
+                meals,
+                skipped,
+                regular,
+                ..
+            } => assert_eq!((2.0, 1.0, false), (meals, skipped, regular)),
+            log_line => panic!("expected meals, got {}", log_line.kind()),
+        }
+    }
+
+    #[test]
+    fn parse_locally_before_the_model() {
+        let questionnaire = Questionnaire::builtin();
+        let now = DateTime::parse_from_rfc3339("2025-03-08T15:15:00-08:00").unwrap();
+        let last_slept = questionnaire.get("last-slept").unwrap();
+        let parsed = last_slept.parse_locally("woke up at 7:30 this morning", now);
+        assert_eq!(Some(7.75), parsed.and_then(|p| p["awake_hours"].as_f64()));
+        assert_eq!(None, last_slept.parse_locally("after the alarm", now));
+        let slept = questionnaire.get("slept-how-long").unwrap();
+        let parsed = slept.parse_locally("about 7 and a half hours", now);
+        assert_eq!(Some(7.5), parsed.and_then(|p| p["sleep_hours"].as_f64()));
+        assert_eq!(
+            None,
+            slept.parse_locally("crashed around 2, up at 7ish", now)
+        );
+        let mood = questionnaire.get("mood").unwrap();
+        assert_eq!(None, mood.parse_locally("8", now));
+    }
+
+    #[test]
+    fn edit_reads_the_field_type() {
+        let questionnaire = Questionnaire::builtin();
+        let mood = questionnaire.get("mood").unwrap();
+        let answer = serde_json::json!({
+            "valence": 2, "arousal": 3, "anxiety": 6, "irritability": 1,
+            "emotions": ["tired"], "justification": "Mostly fine.",
+        });
+        let edited = mood.edit(&answer, "valence", " -1 ").unwrap();
+        assert_eq!(-1, edited["valence"]);
+        assert_eq!(answer["anxiety"], edited["anxiety"]);
+        let edited = mood
+            .edit(&answer, "justification", "Worse than I said. ")
+            .unwrap();
+        assert_eq!("Worse than I said.", edited["justification"]);
+        let edited = mood
+            .edit(&answer, "emotions", r#"["tired", "sad"]"#)
+            .unwrap();
+        assert_eq!(serde_json::json!(["tired", "sad"]), edited["emotions"]);
+    }
+
+    #[test]
+    fn edit_rejects_bad_values() {
+        let questionnaire = Questionnaire::builtin();
+        let mood = questionnaire.get("mood").unwrap();
+        let answer = serde_json::json!({
+            "valence": 2, "arousal": 3, "anxiety": 6, "irritability": 1,
+            "emotions": [], "justification": "",
+        });
+        assert!(mood.edit(&answer, "valence", "pretty good").is_err());
+        assert!(mood.edit(&answer, "valence", "9").is_err());
+        assert!(mood.edit(&answer, "mood", "2").is_err());
+        let hygiene = questionnaire.get("hygiene").unwrap();
+        let answer = serde_json::json!({"answer": "GOOD", "justification": ""});
+        assert!(hygiene.edit(&answer, "answer", "GREAT").is_ok());
+        assert!(hygiene.edit(&answer, "answer", "SPOTLESS").is_err());
+    }
+
+    #[test]
+    fn prompt_hash_is_stable() {
+        assert_eq!(0xcbf29ce484222325, fnv1a(""));
+        assert_eq!(0xaf63dc4c8601ec8c, fnv1a("a"));
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::BTreeMap;
+
+use arrrg::CommandLine;
+use chrono::{Datelike, Local, NaiveDate, Weekday};
+
+use crate::query::{parse_day, read_log_lines};
+use crate::transcript::{Dose, LogLine};
+use crate::units;
+
+/// Doses whose quantities differ by less than this fraction are considered the same.
+const DOSE_TOLERANCE: f64 = 0.05;
+
+//////////////////////////////////////////// Prescription //////////////////////////////////////////
+
+/// What should be taken:  `quantity` `units` of `substance`, `times_daily` times on each of
+/// `weekdays` (every day when empty).  An `as_needed` prescription is never missed;
+/// `times_daily` is then the most doses allowed in a day.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Prescription {
+    pub substance: String,
+    pub quantity: f64,
+    pub units: String,
+    pub times_daily: f64,
+    #[serde(default)]
+    pub weekdays: Vec<Weekday>,
+    #[serde(default)]
+    pub as_needed: bool,
+}
+
+impl Prescription {
+    pub fn is_due(&self, day: NaiveDate) -> bool {
+        self.weekdays.is_empty() || self.weekdays.contains(&day.weekday())
+    }
+
+    /// This prescription in the canonical units check-ins are logged in, so that "1 g" is the
+    /// same dose as a logged "1000 mg".
+    pub fn normalized(&self) -> Self {
+        let dose = Dose::Daily {
+            quantity: self.quantity,
+            units: self.units.clone(),
+            times_daily: self.times_daily,
+        };
+        match units::normalize(&self.substance, &dose) {
+            Some((substance, dose)) => Self {
+                substance,
+                quantity: dose.quantity(),
+                units: dose.units().to_string(),
+                ..self.clone()
+            },
+            None => self.clone(),
+        }
+    }
+
+    fn matches(&self, substance: &str) -> bool {
+        self.substance.eq_ignore_ascii_case(substance)
+    }
+}
+
+////////////////////////////////////////////// Regimen /////////////////////////////////////////////
+
+/// A JSON file of prescriptions, e.g.
+///
+/// ```json
+/// {"medications": [{"substance": "sertraline", "quantity": 50, "units": "mg", "times_daily": 1}]}
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Regimen {
+    pub medications: Vec<Prescription>,
+}
+
+impl Regimen {
+    pub fn load(path: &str) -> Result<Self, std::io::Error> {
+        let regimen = std::fs::read_to_string(path)?;
+        serde_json::from_str(&regimen)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    /// Compare what was logged against the regimen for every day from `since` through `until`.
+    pub fn adherence(
+        &self,
+        log_lines: &[LogLine],
+        since: NaiveDate,
+        until: NaiveDate,
+    ) -> Vec<DayAdherence> {
+        let mut taken: BTreeMap<NaiveDate, Vec<(&str, &Dose)>> = BTreeMap::new();
+        for log_line in log_lines {
+            if let (
+                Some(day),
+                LogLine::Medication {
+                    substance, dose, ..
+                },
+            ) = (log_line.day(), log_line)
+            {
+                taken.entry(day).or_default().push((substance, dose));
+            }
+        }
+        let medications = self
+            .medications
+            .iter()
+            .map(Prescription::normalized)
+            .collect::<Vec<_>>();
+        let mut adherence = vec![];
+        for day in since.iter_days().take_while(|day| *day <= until) {
+            let logged = taken.get(&day).map(Vec::as_slice).unwrap_or(&[]);
+            for prescription in medications.iter() {
+                let allowed = if prescription.is_due(day) {
+                    prescription.times_daily
+                } else {
+                    0.0
+                };
+                let expected = if prescription.as_needed { 0.0 } else { allowed };
+                let mut doses = 0.0;
+                let mut mismatches = vec![];
+                for (_, dose) in logged.iter().filter(|(s, _)| prescription.matches(s)) {
+                    let (quantity, units) = (dose.quantity(), dose.units());
+                    doses += dose.doses_taken();
+                    let relative = (quantity - prescription.quantity).abs()
+                        / prescription.quantity.abs().max(f64::EPSILON);
+                    if !units.eq_ignore_ascii_case(&prescription.units) || relative > DOSE_TOLERANCE
+                    {
+                        mismatches.push(format!(
+                            "took {quantity}{units}, prescribed {}{}",
+                            prescription.quantity, prescription.units
+                        ));
+                    }
+                }
+                if expected == 0.0 && doses == 0.0 {
+                    continue;
+                }
+                adherence.push(DayAdherence {
+                    day,
+                    substance: prescription.substance.clone(),
+                    expected,
+                    taken: doses,
+                    missed: (expected - doses).max(0.0),
+                    extra: (doses - allowed).max(0.0),
+                    mismatches,
+                });
+            }
+        }
+        adherence
+    }
+}
+
+//////////////////////////////////////////// DayAdherence //////////////////////////////////////////
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct DayAdherence {
+    pub day: NaiveDate,
+    pub substance: String,
+    pub expected: f64,
+    pub taken: f64,
+    pub missed: f64,
+    pub extra: f64,
+    pub mismatches: Vec<String>,
+}
+
+/// The fraction of expected doses that were taken, as a percentage.  Extra doses do not make up
+/// for missed ones.
+pub fn adherence_percent(days: &[DayAdherence]) -> Option<f64> {
+    let expected = days.iter().map(|d| d.expected).sum::<f64>();
+    if expected <= 0.0 {
+        return None;
+    }
+    let taken = days.iter().map(|d| d.taken.min(d.expected)).sum::<f64>();
+    Some(100.0 * taken / expected)
+}
+
+/////////////////////////////////////////// WeekAdherence //////////////////////////////////////////
+
+/// Adherence over the week starting on Monday `week`.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct WeekAdherence {
+    pub week: NaiveDate,
+    pub percent: f64,
+}
+
+/// Adherence for each week `days` cover, oldest first, so a trend shows where one total would
+/// hide it.  Weeks in which nothing was due are left out.
+pub fn weekly_adherence(days: &[DayAdherence]) -> Vec<WeekAdherence> {
+    let mut weeks: BTreeMap<NaiveDate, Vec<DayAdherence>> = BTreeMap::new();
+    for day in days {
+        let week = day.day.week(Weekday::Mon).first_day();
+        weeks.entry(week).or_default().push(day.clone());
+    }
+    weeks
+        .into_iter()
+        .filter_map(|(week, days)| {
+            adherence_percent(&days).map(|percent| WeekAdherence { week, percent })
+        })
+        .collect()
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    arrrg_derive::CommandLine,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct AdherenceOptions {
+    #[arrrg(optional, "Transcript to read; defaults to $NOTAPSYCH_TRANSCRIPT.")]
+    pub transcript: String,
+    #[arrrg(optional, "Regimen to check against; defaults to $NOTAPSYCH_REGIMEN.")]
+    pub regimen: String,
+    #[arrrg(optional, "First day to check (YYYY-MM-DD); defaults to 30 days ago.")]
+    pub since: String,
+    #[arrrg(optional, "Last day to check (YYYY-MM-DD); defaults to today.")]
+    pub until: String,
+    #[arrrg(optional, "Output format: table or json.")]
+    pub format: String,
+}
+
+pub fn main(args: &[String]) {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (options, free) = AdherenceOptions::from_arguments_relaxed(
+        "USAGE: notapsych adherence [--regimen FILE] [--since DAY] [--until DAY] [--format table|json]",
+        &args,
+    );
+    if !free.is_empty() {
+        eprintln!("command takes no positional arguments");
+        std::process::exit(13);
+    }
+    let regimen_path = if options.regimen.is_empty() {
+        std::env::var("NOTAPSYCH_REGIMEN").unwrap_or_else(|_| {
+            eprintln!("please pass --regimen or set NOTAPSYCH_REGIMEN in your environment");
+            std::process::exit(13);
+        })
+    } else {
+        options.regimen.clone()
+    };
+    let regimen = Regimen::load(&regimen_path).unwrap_or_else(|err| {
+        eprintln!("could not load regimen from {regimen_path}: {err}");
+        std::process::exit(13);
+    });
+    let until = parse_day("--until", &options.until).unwrap_or_else(|| Local::now().date_naive());
+    let since =
+        parse_day("--since", &options.since).unwrap_or_else(|| until - chrono::Days::new(29));
+    let log_lines = read_log_lines(&options.transcript);
+    let days = regimen.adherence(&log_lines, since, until);
+    let percent = adherence_percent(&days);
+    let weekly = weekly_adherence(&days);
+    match options.format.as_str() {
+        "" | "table" => {
+            println!(
+                "{:<10}  {:<20} {:>8} {:>6} {:>6} {:>6}  notes",
+                "day", "substance", "expected", "taken", "missed", "extra"
+            );
+            for day in days.iter() {
+                println!(
+                    "{:<10}  {:<20} {:>8} {:>6} {:>6} {:>6}  {}",
+                    day.day,
+                    day.substance,
+                    day.expected,
+                    day.taken,
+                    day.missed,
+                    day.extra,
+                    day.mismatches.join("; ")
+                );
+            }
+            if !weekly.is_empty() {
+                println!("\n{:<10}  {:>9}", "week of", "adherence");
+                for week in weekly.iter() {
+                    println!("{:<10}  {:>8.1}%", week.week, week.percent);
+                }
+            }
+            match percent {
+                Some(percent) => println!("\nadherence: {percent:.1}%"),
+                None => println!("\nadherence: nothing was due"),
+            }
+        }
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json! {{
+                "since": since,
+                "until": until,
+                "adherence_percent": percent,
+                "weekly": weekly,
+                "days": days,
+            }})
+            .expect("adherence should always serialize")
+        ),
+        format => {
+            eprintln!("unknown format {format:?}; expected table or json");
+            std::process::exit(13);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, d).unwrap()
+    }
+
+    fn taken(d: u32, substance: &str, quantity: f64, units: &str, frequency: &str) -> LogLine {
+        LogLine::Medication {
+            recorded_at: format!("2025-06-{d:02}T09:00:00-07:00"),
+            substance: substance.to_string(),
+            dose: Dose::from_frequency(frequency, quantity, units.to_string(), 1.0),
+            reported: None,
+            justification: String::new(),
+            provenance: vec![],
+        }
+    }
+
+    fn regimen(json: &str) -> Regimen {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn sertraline() -> Regimen {
+        regimen(
+            r#"{"medications": [
+                {"substance": "sertraline", "quantity": 50, "units": "mg", "times_daily": 1}
+            ]}"#,
+        )
+    }
+
+    #[test]
+    fn missed_and_extra_doses() {
+        let log_lines = vec![
+            taken(2, "sertraline", 50.0, "mg", "daily"),
+            taken(3, "sertraline", 50.0, "mg", "daily"),
+            taken(3, "Sertraline", 50.0, "mg", "once"),
+        ];
+        let days = sertraline().adherence(&log_lines, day(1), day(3));
+        assert_eq!(3, days.len());
+        assert_eq!((1.0, 0.0), (days[0].missed, days[0].extra));
+        assert_eq!((0.0, 0.0), (days[1].missed, days[1].extra));
+        assert_eq!((0.0, 1.0), (days[2].missed, days[2].extra));
+        assert!(days.iter().all(|day| day.mismatches.is_empty()));
+    }
+
+    #[test]
+    fn dose_and_unit_mismatches() {
+        let log_lines = vec![
+            taken(1, "sertraline", 100.0, "mg", "daily"),
+            taken(2, "sertraline", 50.0, "mL", "daily"),
+            taken(3, "sertraline", 51.0, "mg", "daily"),
+        ];
+        let days = sertraline().adherence(&log_lines, day(1), day(3));
+        assert_eq!(vec!["took 100mg, prescribed 50mg"], days[0].mismatches);
+        assert_eq!(vec!["took 50mL, prescribed 50mg"], days[1].mismatches);
+        assert!(days[2].mismatches.is_empty());
+    }
+
+    #[test]
+    fn prescriptions_compare_in_canonical_units() {
+        let regimen = regimen(
+            r#"{"medications": [
+                {"substance": "acetaminophen", "quantity": 1, "units": "g", "times_daily": 1}
+            ]}"#,
+        );
+        let log_lines = vec![taken(1, "acetaminophen", 1000.0, "mg", "daily")];
+        let days = regimen.adherence(&log_lines, day(1), day(1));
+        assert!(days[0].mismatches.is_empty(), "{:?}", days[0].mismatches);
+        assert_eq!(1.0, days[0].taken);
+    }
+
+    #[test]
+    fn as_needed_is_never_missed() {
+        let regimen = regimen(
+            r#"{"medications": [{"substance": "ibuprofen", "quantity": 200, "units": "mg",
+                "times_daily": 2, "as_needed": true}]}"#,
+        );
+        let log_lines = vec![
+            taken(2, "ibuprofen", 200.0, "mg", "as-needed"),
+            taken(3, "ibuprofen", 200.0, "mg", "as-needed"),
+            taken(3, "ibuprofen", 200.0, "mg", "as-needed"),
+            taken(3, "ibuprofen", 200.0, "mg", "as-needed"),
+        ];
+        let days = regimen.adherence(&log_lines, day(1), day(3));
+        // Nothing was due on the 1st, so it is not reported at all.
+        assert_eq!(
+            vec![day(2), day(3)],
+            days.iter().map(|d| d.day).collect::<Vec<_>>()
+        );
+        assert!(days.iter().all(|day| day.missed == 0.0));
+        assert_eq!((0.0, 1.0), (days[0].extra, days[1].extra));
+        assert_eq!(None, adherence_percent(&days));
+    }
+
+    #[test]
+    fn percent_does_not_credit_extra_doses() {
+        let log_lines = vec![
+            taken(2, "sertraline", 50.0, "mg", "daily"),
+            taken(2, "sertraline", 50.0, "mg", "once"),
+            taken(3, "sertraline", 50.0, "mg", "daily"),
+            taken(4, "sertraline", 50.0, "mg", "daily"),
+        ];
+        let days = sertraline().adherence(&log_lines, day(1), day(4));
+        assert_eq!(Some(75.0), adherence_percent(&days));
+        assert_eq!(None, adherence_percent(&[]));
+    }
+
+    #[test]
+    fn weekly_percentages() {
+        // 2025-06-02 and 2025-06-09 are Mondays.
+        let log_lines = (2..=8)
+            .chain(9..=11)
+            .map(|d| taken(d, "sertraline", 50.0, "mg", "daily"))
+            .collect::<Vec<_>>();
+        let days = sertraline().adherence(&log_lines, day(2), day(15));
+        let weekly = weekly_adherence(&days);
+        assert_eq!(
+            vec![
+                WeekAdherence {
+                    week: day(2),
+                    percent: 100.0
+                },
+                WeekAdherence {
+                    week: day(9),
+                    percent: 300.0 / 7.0
+                },
+            ],
+            weekly
+        );
+    }
+}
@@ -0,0 +1,750 @@
+use std::collections::BTreeMap;
+
+use arrrg::CommandLine;
+use chrono::{Local, NaiveDate};
+
+use crate::alcohol::{hours_to_sober, widmark_peak_bac, Profile};
+use crate::query::{parse_day, read_log_lines};
+use crate::sleep::{measured_hours, reported_hours};
+use crate::transcript::LogLine;
+use crate::units::STANDARD_DRINKS;
+
+/// The rolling windows every report covers, in days.
+pub const WINDOWS: &[u64] = &[7, 30];
+
+/// Days where self-reported and device-measured sleep differ by at least this many hours are
+/// called out.
+pub const DISCREPANCY_HOURS: f64 = 1.0;
+
+const SPARKS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/////////////////////////////////////////////// Stats //////////////////////////////////////////////
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Stats {
+    pub count: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub variance: f64,
+}
+
+impl Stats {
+    /// Summarize `samples`, or None when there are none.  Variance is the population variance.
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let count = samples.len();
+        let mean = samples.iter().sum::<f64>() / count as f64;
+        let variance = samples.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / count as f64;
+        let min = samples.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = samples.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        Some(Self {
+            count,
+            mean,
+            min,
+            max,
+            variance,
+        })
+    }
+}
+
+/////////////////////////////////////////////// Window /////////////////////////////////////////////
+
+/// Sleep statistics over the `days` days ending on (and including) the report's last day.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Window {
+    pub days: u64,
+    pub sleep_hours: Option<Stats>,
+    pub measured_sleep_hours: Option<Stats>,
+    pub sleep_quality: Option<Stats>,
+    pub valence: Option<Stats>,
+    pub anxiety: Option<Stats>,
+    pub exercise_minutes: Option<Stats>,
+    pub standard_drinks: Option<Stats>,
+    pub skipped_medication: Option<Stats>,
+}
+
+///////////////////////////////////////// SleepDiscrepancy /////////////////////////////////////////
+
+/// A day where what the user said they slept and what a device measured disagree.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct SleepDiscrepancy {
+    pub day: NaiveDate,
+    pub reported_hours: f64,
+    pub measured_hours: f64,
+}
+
+/////////////////////////////////////////// SubstanceTotal /////////////////////////////////////////
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct SubstanceTotal {
+    pub day: NaiveDate,
+    pub substance: String,
+    pub units: String,
+    pub total: f64,
+}
+
+/////////////////////////////////////////// AlcoholEpisode /////////////////////////////////////////
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct AlcoholEpisode {
+    pub started_at: String,
+    pub standard_drinks: f64,
+    pub duration_hours: f64,
+    pub peak_bac: Option<f64>,
+    /// Hours from the end of the episode until the estimated BAC is back to zero.
+    pub hours_to_sober: Option<f64>,
+}
+
+////////////////////////////////////////// ScreeningTrend //////////////////////////////////////////
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct ScreeningScore {
+    pub day: NaiveDate,
+    pub total: u32,
+    pub severity: String,
+}
+
+/// Every score for one instrument through the report's last day, oldest first.  Screenings are
+/// infrequent, so trends are not limited to the report's windows.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct ScreeningTrend {
+    pub instrument: String,
+    pub scores: Vec<ScreeningScore>,
+}
+
+impl ScreeningTrend {
+    /// The change from the previous score to the latest, if there have been two.
+    pub fn change(&self) -> Option<i64> {
+        match self.scores.as_slice() {
+            [.., previous, latest] => Some(latest.total as i64 - previous.total as i64),
+            _ => None,
+        }
+    }
+}
+
+/////////////////////////////////////////////// Report /////////////////////////////////////////////
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Report {
+    pub until: NaiveDate,
+    pub windows: Vec<Window>,
+    pub substances: Vec<SubstanceTotal>,
+    pub hygiene: BTreeMap<String, usize>,
+    pub alcohol: Vec<AlcoholEpisode>,
+    pub screenings: Vec<ScreeningTrend>,
+    pub sleep_discrepancies: Vec<SleepDiscrepancy>,
+    pub sleep_hours_daily: Vec<Option<f64>>,
+    pub measured_sleep_hours_daily: Vec<Option<f64>>,
+    pub sleep_quality_daily: Vec<Option<f64>>,
+}
+
+impl Report {
+    /// Build a report over `log_lines` for the windows ending on `until`.  Substance totals and
+    /// hygiene cover the widest window; the daily series cover it one value per day, oldest first.
+    /// Peak BAC is only estimated when a `profile` is given.
+    pub fn new(log_lines: &[LogLine], until: NaiveDate, profile: Option<&Profile>) -> Self {
+        let widest = WINDOWS.iter().copied().max().unwrap_or(0);
+        let reported_nightly = reported_hours(log_lines);
+        let measured_nightly = measured_hours(log_lines);
+        let windows = WINDOWS
+            .iter()
+            .map(|&days| {
+                let since = first_day(until, days);
+                Window {
+                    days,
+                    sleep_hours: Stats::from_samples(&nights(&reported_nightly, since, until)),
+                    measured_sleep_hours: Stats::from_samples(&nights(
+                        &measured_nightly,
+                        since,
+                        until,
+                    )),
+                    sleep_quality: Stats::from_samples(&samples(
+                        log_lines,
+                        since,
+                        until,
+                        sleep_quality,
+                    )),
+                    valence: Stats::from_samples(&samples(log_lines, since, until, valence)),
+                    anxiety: Stats::from_samples(&samples(log_lines, since, until, anxiety)),
+                    exercise_minutes: Stats::from_samples(&samples(
+                        log_lines,
+                        since,
+                        until,
+                        exercise_minutes,
+                    )),
+                    standard_drinks: Stats::from_samples(&samples(
+                        log_lines,
+                        since,
+                        until,
+                        standard_drinks,
+                    )),
+                    skipped_medication: Stats::from_samples(&samples(
+                        log_lines,
+                        since,
+                        until,
+                        skipped_medication,
+                    )),
+                }
+            })
+            .collect();
+        let since = first_day(until, widest);
+        let mut substances: BTreeMap<(NaiveDate, String, String), f64> = BTreeMap::new();
+        let mut hygiene = BTreeMap::new();
+        let mut alcohol = vec![];
+        let mut screenings: BTreeMap<String, Vec<ScreeningScore>> = BTreeMap::new();
+        for log_line in log_lines {
+            let Some(day) = log_line.day() else {
+                continue;
+            };
+            if let LogLine::Screening {
+                instrument,
+                total,
+                severity,
+                ..
+            } = log_line
+            {
+                if day <= until {
+                    screenings
+                        .entry(instrument.clone())
+                        .or_default()
+                        .push(ScreeningScore {
+                            day,
+                            total: *total,
+                            severity: severity.clone(),
+                        });
+                }
+            }
+            if day < since || day > until {
+                continue;
+            }
+            match log_line {
+                LogLine::Medication {
+                    substance, dose, ..
+                } => {
+                    *substances
+                        .entry((day, substance.to_lowercase(), dose.units().to_string()))
+                        .or_insert(0.0) += dose.total();
+                }
+                LogLine::Hygiene { hygiene: h, .. } => {
+                    *hygiene.entry(h.clone()).or_insert(0) += 1;
+                }
+                LogLine::Alcohol {
+                    standard_drinks,
+                    started_at,
+                    duration_hours,
+                    ..
+                } => {
+                    *substances
+                        .entry((day, "alcohol".to_string(), STANDARD_DRINKS.to_string()))
+                        .or_insert(0.0) += standard_drinks;
+                    let peak_bac = profile.map(|profile| {
+                        widmark_peak_bac(*standard_drinks, *duration_hours, profile)
+                    });
+                    alcohol.push(AlcoholEpisode {
+                        started_at: started_at.clone(),
+                        standard_drinks: *standard_drinks,
+                        duration_hours: *duration_hours,
+                        peak_bac,
+                        hours_to_sober: peak_bac.map(hours_to_sober),
+                    });
+                }
+                _ => {}
+            }
+        }
+        let substances = substances
+            .into_iter()
+            .map(|((day, substance, units), total)| SubstanceTotal {
+                day,
+                substance,
+                units,
+                total,
+            })
+            .collect();
+        let screenings = screenings
+            .into_iter()
+            .map(|(instrument, mut scores)| {
+                scores.sort_by_key(|score| score.day);
+                ScreeningTrend { instrument, scores }
+            })
+            .collect();
+        let sleep_hours_daily = daily(&reported_nightly, since, until);
+        let measured_sleep_hours_daily = daily(&measured_nightly, since, until);
+        let sleep_discrepancies = since
+            .iter_days()
+            .zip(
+                sleep_hours_daily
+                    .iter()
+                    .zip(measured_sleep_hours_daily.iter()),
+            )
+            .filter_map(|(day, pair)| match pair {
+                (Some(reported), Some(measured))
+                    if (reported - measured).abs() >= DISCREPANCY_HOURS =>
+                {
+                    Some(SleepDiscrepancy {
+                        day,
+                        reported_hours: *reported,
+                        measured_hours: *measured,
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+        Self {
+            until,
+            windows,
+            substances,
+            hygiene,
+            alcohol,
+            screenings,
+            sleep_discrepancies,
+            sleep_hours_daily,
+            measured_sleep_hours_daily,
+            sleep_quality_daily: daily_means(log_lines, since, until, sleep_quality),
+        }
+    }
+
+    pub fn render(&self, sparklines: bool) -> String {
+        let mut out = format!("Report through {}\n\n", self.until);
+        out += &format!(
+            "{:<16} {:>6} {:>5} {:>7} {:>7} {:>7} {:>8}\n",
+            "metric", "window", "n", "mean", "min", "max", "variance"
+        );
+        for window in self.windows.iter() {
+            for (metric, stats) in [
+                ("sleep hours", &window.sleep_hours),
+                ("measured sleep", &window.measured_sleep_hours),
+                ("sleep quality", &window.sleep_quality),
+                ("valence", &window.valence),
+                ("anxiety", &window.anxiety),
+                ("exercise minutes", &window.exercise_minutes),
+                ("standard drinks", &window.standard_drinks),
+                ("skipped doses", &window.skipped_medication),
+            ] {
+                match stats {
+                    Some(stats) => {
+                        out += &format!(
+                            "{:<16} {:>5}d {:>5} {:>7.2} {:>7.2} {:>7.2} {:>8.2}\n",
+                            metric,
+                            window.days,
+                            stats.count,
+                            stats.mean,
+                            stats.min,
+                            stats.max,
+                            stats.variance
+                        );
+                    }
+                    None => {
+                        out += &format!("{:<16} {:>5}d {:>5}\n", metric, window.days, 0);
+                    }
+                }
+            }
+        }
+        if sparklines {
+            out.push('\n');
+            out += &format!(
+                "{:<16} {}\n",
+                "sleep hours",
+                sparkline(&self.sleep_hours_daily)
+            );
+            if self.measured_sleep_hours_daily.iter().any(Option::is_some) {
+                out += &format!(
+                    "{:<16} {}\n",
+                    "measured sleep",
+                    sparkline(&self.measured_sleep_hours_daily)
+                );
+            }
+            out += &format!(
+                "{:<16} {}\n",
+                "sleep quality",
+                sparkline(&self.sleep_quality_daily)
+            );
+        }
+        if !self.sleep_discrepancies.is_empty() {
+            out += &format!(
+                "\n{:<10}  {:>8} {:>8} {:>7}\n",
+                "day", "reported", "measured", "diff"
+            );
+            for discrepancy in self.sleep_discrepancies.iter() {
+                out += &format!(
+                    "{:<10}  {:>8.2} {:>8.2} {:>+7.2}\n",
+                    discrepancy.day,
+                    discrepancy.reported_hours,
+                    discrepancy.measured_hours,
+                    discrepancy.reported_hours - discrepancy.measured_hours
+                );
+            }
+        }
+        if !self.substances.is_empty() {
+            out += &format!(
+                "\n{:<10}  {:<20} {:>10} {}\n",
+                "day", "substance", "total", "units"
+            );
+            for total in self.substances.iter() {
+                out += &format!(
+                    "{:<10}  {:<20} {:>10.2} {}\n",
+                    total.day, total.substance, total.total, total.units
+                );
+            }
+        }
+        if !self.alcohol.is_empty() {
+            out += &format!(
+                "\n{:<25}  {:>6} {:>8} {:>8} {:>8}\n",
+                "drinking started", "drinks", "hours", "peak BAC", "sober in"
+            );
+            for episode in self.alcohol.iter() {
+                out += &format!(
+                    "{:<25}  {:>6.1} {:>8.1} {:>8} {:>8}\n",
+                    episode.started_at,
+                    episode.standard_drinks,
+                    episode.duration_hours,
+                    episode
+                        .peak_bac
+                        .map(|bac| format!("{bac:.3}"))
+                        .unwrap_or_else(|| "-".to_string()),
+                    episode
+                        .hours_to_sober
+                        .map(|hours| format!("{hours:.1}h"))
+                        .unwrap_or_else(|| "-".to_string())
+                );
+            }
+        }
+        if !self.screenings.is_empty() {
+            out += &format!(
+                "\n{:<10} {:<10} {:>6} {:<18} {:>6}  {}\n",
+                "instrument", "last", "score", "severity", "change", "trend"
+            );
+            for trend in self.screenings.iter() {
+                let Some(latest) = trend.scores.last() else {
+                    continue;
+                };
+                let series = trend
+                    .scores
+                    .iter()
+                    .map(|score| Some(score.total as f64))
+                    .collect::<Vec<_>>();
+                out += &format!(
+                    "{:<10} {:<10} {:>6} {:<18} {:>6}  {}\n",
+                    trend.instrument,
+                    latest.day,
+                    latest.total,
+                    latest.severity,
+                    trend
+                        .change()
+                        .map(|change| format!("{change:+}"))
+                        .unwrap_or_else(|| "-".to_string()),
+                    sparkline(&series)
+                );
+            }
+        }
+        if !self.hygiene.is_empty() {
+            out += &format!("\n{:<10} {:>5}\n", "hygiene", "count");
+            for (hygiene, count) in self.hygiene.iter() {
+                out += &format!("{hygiene:<10} {count:>5}\n");
+            }
+        }
+        out
+    }
+}
+
+/// The first day of a `days`-long window ending on `until`.
+pub fn first_day(until: NaiveDate, days: u64) -> NaiveDate {
+    until - chrono::Days::new(days.saturating_sub(1))
+}
+
+fn sleep_quality(log_line: &LogLine) -> Option<f64> {
+    match log_line {
+        LogLine::SleepQuality { answer, .. } => Some(*answer),
+        _ => log_line.signal("sleep-quality"),
+    }
+}
+
+fn valence(log_line: &LogLine) -> Option<f64> {
+    match log_line {
+        LogLine::Mood { valence, .. } => Some(*valence),
+        _ => log_line.signal("valence"),
+    }
+}
+
+fn anxiety(log_line: &LogLine) -> Option<f64> {
+    match log_line {
+        LogLine::Mood { anxiety, .. } => Some(*anxiety),
+        _ => log_line.signal("anxiety"),
+    }
+}
+
+fn exercise_minutes(log_line: &LogLine) -> Option<f64> {
+    match log_line {
+        LogLine::Exercise {
+            duration_minutes, ..
+        } => Some(*duration_minutes),
+        _ => log_line.signal("exercise-minutes"),
+    }
+}
+
+/// Drinks per episode, whether logged at a check-in or mentioned in a journal.
+fn standard_drinks(log_line: &LogLine) -> Option<f64> {
+    match log_line {
+        LogLine::Alcohol {
+            standard_drinks, ..
+        } => Some(*standard_drinks),
+        _ => log_line.signal("standard-drinks"),
+    }
+}
+
+/// Doses the user said in a journal they skipped; check-ins track adherence against a regimen.
+fn skipped_medication(log_line: &LogLine) -> Option<f64> {
+    log_line.signal("skipped-medication")
+}
+
+fn samples(
+    log_lines: &[LogLine],
+    since: NaiveDate,
+    until: NaiveDate,
+    metric: fn(&LogLine) -> Option<f64>,
+) -> Vec<f64> {
+    log_lines
+        .iter()
+        .filter(|log_line| {
+            log_line
+                .day()
+                .map(|day| since <= day && day <= until)
+                .unwrap_or(false)
+        })
+        .filter_map(metric)
+        .collect()
+}
+
+/// The nightly values from `since` through `until`.
+fn nights(nightly: &BTreeMap<NaiveDate, f64>, since: NaiveDate, until: NaiveDate) -> Vec<f64> {
+    nightly
+        .range(since..=until)
+        .map(|(_, hours)| *hours)
+        .collect()
+}
+
+/// One nightly value per day from `since` through `until`, None for nights without one.
+fn daily(
+    nightly: &BTreeMap<NaiveDate, f64>,
+    since: NaiveDate,
+    until: NaiveDate,
+) -> Vec<Option<f64>> {
+    since
+        .iter_days()
+        .take_while(|day| *day <= until)
+        .map(|day| nightly.get(&day).copied())
+        .collect()
+}
+
+/// One mean per day from `since` through `until`, None for days without samples.
+pub fn daily_means(
+    log_lines: &[LogLine],
+    since: NaiveDate,
+    until: NaiveDate,
+    metric: fn(&LogLine) -> Option<f64>,
+) -> Vec<Option<f64>> {
+    let mut by_day: BTreeMap<NaiveDate, Vec<f64>> = BTreeMap::new();
+    for log_line in log_lines {
+        if let (Some(day), Some(value)) = (log_line.day(), metric(log_line)) {
+            by_day.entry(day).or_default().push(value);
+        }
+    }
+    since
+        .iter_days()
+        .take_while(|day| *day <= until)
+        .map(|day| {
+            by_day
+                .get(&day)
+                .map(|values| values.iter().sum::<f64>() / values.len() as f64)
+        })
+        .collect()
+}
+
+/// Render a series as a sparkline scaled between its own min and max, with gaps for None.
+pub fn sparkline(series: &[Option<f64>]) -> String {
+    let values = series.iter().flatten().copied().collect::<Vec<_>>();
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    series
+        .iter()
+        .map(|value| match value {
+            Some(value) if max > min => {
+                let idx = ((value - min) / (max - min) * (SPARKS.len() - 1) as f64).round();
+                SPARKS[idx as usize]
+            }
+            Some(_) => SPARKS[SPARKS.len() / 2],
+            None => ' ',
+        })
+        .collect()
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    arrrg_derive::CommandLine,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct ReportOptions {
+    #[arrrg(optional, "Transcript to read; defaults to $NOTAPSYCH_TRANSCRIPT.")]
+    pub transcript: String,
+    #[arrrg(optional, "Last day of the report (YYYY-MM-DD); defaults to today.")]
+    pub until: String,
+    #[arrrg(optional, "Output format: table or json.")]
+    pub format: String,
+    #[arrrg(flag, "Draw sparklines of the daily sleep series.")]
+    pub sparklines: bool,
+}
+
+pub fn main(args: &[String]) {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (options, free) = ReportOptions::from_arguments_relaxed(
+        "USAGE: notapsych report [--until DAY] [--format table|json] [--sparklines]",
+        &args,
+    );
+    if !free.is_empty() {
+        eprintln!("command takes no positional arguments");
+        std::process::exit(13);
+    }
+    let until = parse_day("--until", &options.until).unwrap_or_else(|| Local::now().date_naive());
+    let log_lines = read_log_lines(&options.transcript);
+    let profile = Profile::from_env();
+    let report = Report::new(&log_lines, until, profile.as_ref());
+    match options.format.as_str() {
+        "" | "table" => print!("{}", report.render(options.sparklines)),
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("report should always serialize")
+        ),
+        format => {
+            eprintln!("unknown format {format:?}; expected table or json");
+            std::process::exit(13);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn line(json: serde_json::Value) -> LogLine {
+        line_on(until(), json)
+    }
+
+    fn line_on(day: NaiveDate, json: serde_json::Value) -> LogLine {
+        let recorded_at = Local
+            .from_local_datetime(&day.and_hms_opt(12, 0, 0).unwrap())
+            .unwrap()
+            .fixed_offset()
+            .to_rfc3339();
+        let mut json = json;
+        json["recorded_at"] = recorded_at.into();
+        json["justification"] = "".into();
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn until() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, 10).unwrap()
+    }
+
+    fn valence_on(month: u32, day: u32, valence: f64) -> LogLine {
+        line_on(
+            NaiveDate::from_ymd_opt(2025, month, day).unwrap(),
+            serde_json::json!({
+                "type": "mood", "valence": valence, "arousal": 5, "anxiety": 0,
+                "irritability": 0,
+            }),
+        )
+    }
+
+    #[test]
+    fn stats_from_samples() {
+        assert_eq!(None, Stats::from_samples(&[]));
+        let stats = Stats::from_samples(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]).unwrap();
+        assert_eq!(8, stats.count);
+        assert_eq!(5.0, stats.mean);
+        assert_eq!(2.0, stats.min);
+        assert_eq!(9.0, stats.max);
+        assert_eq!(4.0, stats.variance);
+    }
+
+    #[test]
+    fn first_day_includes_until() {
+        assert_eq!(until(), first_day(until(), 1));
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2025, 6, 4),
+            Some(first_day(until(), 7))
+        );
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2025, 5, 12),
+            Some(first_day(until(), 30))
+        );
+    }
+
+    #[test]
+    fn window_boundaries() {
+        let log_lines = vec![
+            valence_on(5, 11, -5.0),
+            valence_on(5, 12, -2.0),
+            valence_on(6, 3, 0.0),
+            valence_on(6, 4, 1.0),
+            valence_on(6, 10, 3.0),
+            valence_on(6, 11, 5.0),
+        ];
+        let report = Report::new(&log_lines, until(), None);
+        let summary = |days: u64| {
+            let window = report.windows.iter().find(|w| w.days == days).unwrap();
+            let stats = window.valence.as_ref().unwrap();
+            (stats.count, stats.mean, stats.min, stats.max)
+        };
+        assert_eq!((2, 2.0, 1.0, 3.0), summary(7));
+        assert_eq!((4, 0.5, -2.0, 3.0), summary(30));
+    }
+
+    #[test]
+    fn empty_window_has_no_stats() {
+        let log_lines = vec![valence_on(5, 1, 1.0)];
+        let report = Report::new(&log_lines, until(), None);
+        assert!(report.windows.iter().all(|window| window.valence.is_none()));
+    }
+
+    #[test]
+    fn journal_signals_join_check_ins() {
+        let log_lines = vec![
+            line(serde_json::json!({
+                "type": "mood", "valence": 3, "arousal": 5, "anxiety": 2, "irritability": 0,
+            })),
+            line(serde_json::json!({
+                "type": "exercise", "activity": "run", "duration_minutes": 30,
+                "intensity": "vigorous",
+            })),
+            line(serde_json::json!({
+                "type": "journal", "text": "rough day",
+                "signals": [
+                    {"metric": "valence", "value": -1},
+                    {"metric": "anxiety", "value": 6},
+                    {"metric": "exercise-minutes", "value": 20},
+                    {"metric": "standard-drinks", "value": 3},
+                    {"metric": "skipped-medication", "value": 1},
+                ],
+            })),
+        ];
+        let report = Report::new(&log_lines, until(), None);
+        let window = &report.windows[0];
+        let mean = |stats: &Option<Stats>| stats.as_ref().map(|stats| (stats.count, stats.mean));
+        assert_eq!(Some((2, 1.0)), mean(&window.valence));
+        assert_eq!(Some((2, 4.0)), mean(&window.anxiety));
+        assert_eq!(Some((2, 25.0)), mean(&window.exercise_minutes));
+        assert_eq!(Some((1, 3.0)), mean(&window.standard_drinks));
+        assert_eq!(Some((1, 1.0)), mean(&window.skipped_medication));
+        let rendered = report.render(false);
+        assert!(rendered.contains("skipped doses"), "{rendered}");
+    }
+}
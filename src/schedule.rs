@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+
+use chrono::{Days, Months, NaiveDate};
+
+use crate::questionnaire::{fnv1a, Question, Questionnaire};
+use crate::transcript::{Entry, LogLine, Transcript};
+
+////////////////////////////////////////////// Cadence /////////////////////////////////////////////
+
+/// How often a question is asked.  In a questionnaire file this is `cadence = "weekly"`, or
+/// `cadence = { random = 0.25 }` to ask on roughly a quarter of days.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cadence {
+    #[default]
+    Daily,
+    Weekly,
+    Monthly,
+    Random(f64),
+}
+
+impl Cadence {
+    /// Whether a question last answered on `last` should be asked on `today`.  Random questions
+    /// are sampled once per question per day, so checking in twice does not get a second roll.
+    pub fn is_due(&self, slug: &str, last: Option<NaiveDate>, today: NaiveDate) -> bool {
+        if last.map(|last| last >= today).unwrap_or(false) {
+            return false;
+        }
+        match self {
+            Cadence::Daily => true,
+            Cadence::Weekly => match (last, today.checked_sub_days(Days::new(7))) {
+                (Some(last), Some(week_ago)) => last <= week_ago,
+                _ => true,
+            },
+            Cadence::Monthly => match (last, today.checked_sub_months(Months::new(1))) {
+                (Some(last), Some(month_ago)) => last <= month_ago,
+                _ => true,
+            },
+            Cadence::Random(probability) => sample(slug, today) < *probability,
+        }
+    }
+}
+
+/// A number in [0, 1) that depends only on `slug` and `day`.
+fn sample(slug: &str, day: NaiveDate) -> f64 {
+    (fnv1a(&format!("{slug}:{day}")) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+///////////////////////////////////////////// Schedule /////////////////////////////////////////////
+
+/// The last day each kind of log line was answered in a check-in.  Imported lines are skipped so a
+/// morning sync from a tracker does not stand in for the user's own answer.
+pub fn last_answered(log_lines: &[LogLine]) -> BTreeMap<&'static str, NaiveDate> {
+    let mut last = BTreeMap::new();
+    for log_line in log_lines {
+        if log_line.imported_from().is_some() {
+            continue;
+        }
+        if let Some(day) = log_line.day() {
+            let entry = last.entry(log_line.kind()).or_insert(day);
+            *entry = (*entry).max(day);
+        }
+    }
+    last
+}
+
+/// Read the log lines of the transcript at `NOTAPSYCH_TRANSCRIPT` for scheduling.  A transcript
+/// that does not exist yet means nothing has been answered.
+pub fn answered_from_env() -> Vec<LogLine> {
+    match Transcript::from_env() {
+        Ok(transcript) => transcript
+            .filter_map(|entry| match entry {
+                Entry::LogLine(log_line) => Some(log_line),
+                _ => None,
+            })
+            .collect(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(err) => {
+            eprintln!("could not open transcript: {err}");
+            std::process::exit(13);
+        }
+    }
+}
+
+/// The check-in questions due on `today`, in questionnaire order.  A question counts as answered
+/// on the last day a line of its log type was recorded in a check-in.
+pub fn due<'a>(
+    questionnaire: &'a Questionnaire,
+    log_lines: &[LogLine],
+    today: NaiveDate,
+) -> Vec<&'a Question> {
+    let last = last_answered(log_lines);
+    questionnaire
+        .checkin()
+        .filter(|q| {
+            q.cadence
+                .is_due(&q.slug, last.get(q.log.as_str()).copied(), today)
+        })
+        .collect()
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    arrrg_derive::CommandLine,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct CheckinOptions {
+    #[arrrg(flag, "Ask every question, not just the ones due today.")]
+    pub all: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, d).unwrap()
+    }
+
+    fn hours_slept(d: u32, imported_from: Option<&str>) -> LogLine {
+        LogLine::HoursSlept {
+            recorded_at: format!("2025-06-{d:02}T08:00:00-07:00"),
+            sleep_hours: 7.0,
+            justification: String::new(),
+            imported_from: imported_from.map(str::to_string),
+            measured: imported_from.is_some(),
+            provenance: vec![],
+        }
+    }
+
+    #[test]
+    fn weekly_waits_seven_days() {
+        let weekly = Cadence::Weekly;
+        assert!(weekly.is_due("hygiene", None, day(10)));
+        assert!(!weekly.is_due("hygiene", Some(day(4)), day(10)));
+        assert!(weekly.is_due("hygiene", Some(day(3)), day(10)));
+    }
+
+    #[test]
+    fn nothing_is_due_twice_a_day() {
+        assert!(!Cadence::Daily.is_due("mood", Some(day(10)), day(10)));
+        assert!(!Cadence::Random(1.0).is_due("mood", Some(day(10)), day(10)));
+    }
+
+    #[test]
+    fn random_is_stable_per_day() {
+        for d in 1..=30 {
+            assert_eq!(sample("meals", day(d)), sample("meals", day(d)));
+            assert!((0.0..1.0).contains(&sample("meals", day(d))));
+        }
+        assert!(!Cadence::Random(0.0).is_due("meals", None, day(10)));
+        assert!(Cadence::Random(1.0).is_due("meals", None, day(10)));
+    }
+
+    #[test]
+    fn imports_do_not_count_as_answers() {
+        let log_lines = vec![hours_slept(9, None), hours_slept(10, Some("fitbit"))];
+        let last = last_answered(&log_lines);
+        assert_eq!(Some(&day(9)), last.get("hours-slept"));
+        let questionnaire = Questionnaire::builtin();
+        let due = due(&questionnaire, &log_lines, day(10));
+        assert!(due.iter().any(|q| q.slug == "slept-how-long"));
+    }
+}
@@ -0,0 +1,157 @@
+use arrrg::CommandLine;
+
+use crate::query::transcript_path;
+use crate::transcript::{LogLine, SCHEMA_VERSION};
+
+/// The JSON Schema every transcript line conforms to, generated from [LogLine] so it cannot
+/// drift from what serde reads and writes.  Lines written before versioning lack
+/// `schema_version`, so it is described but not required.
+///
+/// `schema/transcript.v{SCHEMA_VERSION}.json` is this schema checked in for tools that do not
+/// link this crate; regenerate it with `notapsych schema` when bumping the version.
+pub fn schema() -> serde_json::Value {
+    let mut schema = serde_json::to_value(schemars::schema_for!(LogLine))
+        .expect("schema should always serialize");
+    let root = schema
+        .as_object_mut()
+        .expect("a schema for an enum should be an object");
+    root.insert(
+        "title".to_string(),
+        format!("notapsychai transcript line, version {SCHEMA_VERSION}").into(),
+    );
+    root.insert(
+        "properties".to_string(),
+        serde_json::json! {{
+            "schema_version": {
+                "description": "The version of this schema the line was written at.",
+                "type": "integer",
+                "const": SCHEMA_VERSION,
+            }
+        }},
+    );
+    schema
+}
+
+/// Check every line of a JSONL transcript against [schema], returning one message per problem.
+pub fn validate(transcript: &str) -> Vec<String> {
+    let schema = schema();
+    let validator = jsonschema::validator_for(&schema).expect("generated schema should compile");
+    let mut problems = vec![];
+    for (idx, text) in transcript.lines().enumerate() {
+        let line = idx + 1;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let instance = match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(instance) => instance,
+            Err(err) => {
+                problems.push(format!("line {line}: not JSON: {err}"));
+                continue;
+            }
+        };
+        for error in validator.iter_errors(&instance) {
+            let path = error.instance_path.to_string();
+            if path.is_empty() {
+                problems.push(format!("line {line}: {error}"));
+            } else {
+                problems.push(format!("line {line}: {path}: {error}"));
+            }
+        }
+    }
+    problems
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    arrrg_derive::CommandLine,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct SchemaOptions {
+    #[arrrg(
+        optional,
+        "JSONL file to validate; defaults to $NOTAPSYCH_TRANSCRIPT with --validate."
+    )]
+    pub transcript: String,
+    #[arrrg(flag, "Validate a transcript instead of printing the schema.")]
+    pub validate: bool,
+}
+
+pub fn main(args: &[String]) {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (options, free) = SchemaOptions::from_arguments_relaxed(
+        "USAGE: notapsych schema [--validate [--transcript FILE]]",
+        &args,
+    );
+    if !free.is_empty() {
+        eprintln!("command takes no positional arguments");
+        std::process::exit(13);
+    }
+    if !options.validate {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&schema()).expect("schema should always serialize")
+        );
+        return;
+    }
+    let path = transcript_path(&options.transcript);
+    let transcript = std::fs::read_to_string(&path).unwrap_or_else(|err| {
+        eprintln!("could not read {path}: {err}");
+        std::process::exit(13);
+    });
+    let problems = validate(&transcript);
+    for problem in problems.iter() {
+        eprintln!("{problem}");
+    }
+    if !problems.is_empty() {
+        std::process::exit(13);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_in_schema_is_current() {
+        let checked_in: serde_json::Value =
+            serde_json::from_str(include_str!("../schema/transcript.v1.json")).unwrap();
+        assert_eq!(1, SCHEMA_VERSION, "check in the schema for the new version");
+        assert_eq!(checked_in, schema(), "regenerate with `notapsych schema`");
+    }
+
+    #[test]
+    fn validate_accepts_valid_lines() {
+        let transcript = r#"{"schema_version": 1, "type": "hygiene", "recorded_at": "2025-06-10T08:00:00-07:00", "hygiene": "GOOD", "justification": ""}
+
+{"type": "last-slept", "recorded_at": "2025-06-10T08:00:00-07:00", "awake_hours": 1.5, "justification": ""}
+"#;
+        assert_eq!(Vec::<String>::new(), validate(transcript));
+    }
+
+    #[test]
+    fn validate_rejects_wrong_schema_version() {
+        let transcript = r#"{"schema_version": 2, "type": "hygiene", "recorded_at": "2025-06-10T08:00:00-07:00", "hygiene": "GOOD", "justification": ""}"#;
+        let problems = validate(transcript);
+        assert!(!problems.is_empty());
+        assert!(
+            problems[0].starts_with("line 1: /schema_version"),
+            "{problems:?}"
+        );
+    }
+
+    #[test]
+    fn validate_rejects_bad_fields() {
+        let transcript = r#"
+{"type": "last-slept", "recorded_at": "2025-06-10T08:00:00-07:00", "awake_hours": "lots", "justification": ""}
+not json"#;
+        let problems = validate(transcript);
+        assert_eq!(2, problems.len(), "{problems:?}");
+        assert!(problems[0].starts_with("line 2: "), "{problems:?}");
+        assert!(problems[1].starts_with("line 3: not JSON"), "{problems:?}");
+    }
+}
@@ -0,0 +1,519 @@
+use std::collections::BTreeMap;
+
+use arrrg::CommandLine;
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveTime, TimeZone, Weekday,
+};
+
+use crate::query::{parse_day, read_log_lines};
+use crate::transcript::LogLine;
+
+/// A last-slept and hours-slept answer recorded this close together belong to the same check-in.
+const SAME_CHECKIN_MINUTES: i64 = 60;
+
+/// The resolution at which the Sleep Regularity Index compares days.
+const SRI_STEP_MINUTES: i64 = 5;
+
+/////////////////////////////////////////// SleepEpisode ///////////////////////////////////////////
+
+/// One reconstructed sleep, from falling asleep to waking, in local time.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct SleepEpisode {
+    pub onset: DateTime<Local>,
+    pub wake: DateTime<Local>,
+}
+
+impl SleepEpisode {
+    pub fn hours(&self) -> f64 {
+        (self.wake - self.onset).num_minutes() as f64 / 60.0
+    }
+
+    /// The day the user woke up on; the night belongs to this day.
+    pub fn day(&self) -> NaiveDate {
+        self.wake.date_naive()
+    }
+
+    /// Mid-sleep as hours relative to local midnight of the wake day, so 23:30 the night before
+    /// is -0.5 and 03:30 is 3.5.
+    pub fn mid_sleep(&self) -> f64 {
+        let mid = self.onset + (self.wake - self.onset) / 2;
+        let midnight = local_midnight(self.day());
+        (mid - midnight).num_minutes() as f64 / 60.0
+    }
+
+    /// Nights before a Saturday or Sunday count as free days.
+    pub fn is_free_day(&self) -> bool {
+        matches!(self.day().weekday(), Weekday::Sat | Weekday::Sun)
+    }
+
+    fn asleep_at(&self, when: DateTime<Local>) -> bool {
+        self.onset <= when && when < self.wake
+    }
+}
+
+fn local_midnight(day: NaiveDate) -> DateTime<Local> {
+    let midnight = day.and_time(NaiveTime::MIN);
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .unwrap_or_else(|| Local.from_utc_datetime(&midnight))
+}
+
+/// Pair each last-slept answer with the hours-slept answer from the same check-in to recover
+/// when the user fell asleep and woke up.
+pub fn episodes(log_lines: &[LogLine]) -> Vec<SleepEpisode> {
+    let mut woke = vec![];
+    let mut slept = vec![];
+    for log_line in log_lines {
+        let Some(when) = log_line.timestamp() else {
+            continue;
+        };
+        match log_line {
+            LogLine::LastSlept { awake_hours, .. } => woke.push((when, *awake_hours)),
+            LogLine::HoursSlept { sleep_hours, .. } => slept.push((when, *sleep_hours)),
+            _ => {}
+        }
+    }
+    let mut episodes = vec![];
+    for (when, awake_hours) in woke {
+        let Some((_, sleep_hours)) = slept
+            .iter()
+            .filter(|(s, _)| (*s - when).num_minutes().abs() <= SAME_CHECKIN_MINUTES)
+            .min_by_key(|(s, _)| (*s - when).num_minutes().abs())
+        else {
+            continue;
+        };
+        let wake = when.with_timezone(&Local) - hours(awake_hours);
+        let onset = wake - hours(*sleep_hours);
+        episodes.push(SleepEpisode { onset, wake });
+    }
+    episodes.sort_by_key(|episode| episode.wake);
+    episodes
+}
+
+fn hours(hours: f64) -> Duration {
+    Duration::minutes((hours * 60.0).round() as i64)
+}
+
+/////////////////////////////////////////////// Debt ///////////////////////////////////////////////
+
+/// Reported sleep per night, keyed by wake day.  A check-in answer beats a journal mention, which
+/// beats another tracker's sleep diary; among answers of the same kind the latest wins, so a
+/// correction replaces what it corrects rather than adding to it.
+pub fn reported_hours(log_lines: &[LogLine]) -> BTreeMap<NaiveDate, f64> {
+    let mut best: BTreeMap<NaiveDate, (u8, DateTime<FixedOffset>, f64)> = BTreeMap::new();
+    for log_line in log_lines {
+        let (Some(day), Some(when)) = (log_line.day(), log_line.timestamp()) else {
+            continue;
+        };
+        let (rank, hours) = match log_line {
+            LogLine::HoursSlept { measured: true, .. } => continue,
+            LogLine::HoursSlept {
+                sleep_hours,
+                imported_from: None,
+                ..
+            } => (0, *sleep_hours),
+            LogLine::HoursSlept { sleep_hours, .. } => (2, *sleep_hours),
+            _ => match log_line.signal("sleep-hours") {
+                Some(hours) => (1, hours),
+                None => continue,
+            },
+        };
+        let current = best.entry(day).or_insert((rank, when, hours));
+        if rank < current.0 || (rank == current.0 && when > current.1) {
+            *current = (rank, when, hours);
+        }
+    }
+    best.into_iter()
+        .map(|(day, (_, _, hours))| (day, hours))
+        .collect()
+}
+
+/// Device-measured sleep per night, keyed by wake day.  Naps and other sources' copies of the
+/// same night are not added to it; the longest measurement stands for the night.
+pub fn measured_hours(log_lines: &[LogLine]) -> BTreeMap<NaiveDate, f64> {
+    let mut nightly: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+    for log_line in log_lines {
+        if let (
+            Some(day),
+            LogLine::HoursSlept {
+                sleep_hours,
+                measured: true,
+                ..
+            },
+        ) = (log_line.day(), log_line)
+        {
+            let longest = nightly.entry(day).or_insert(*sleep_hours);
+            *longest = longest.max(*sleep_hours);
+        }
+    }
+    nightly
+}
+
+/// One value per night:  what the user reported, or what a device measured when they did not.
+pub fn nightly_hours(log_lines: &[LogLine]) -> BTreeMap<NaiveDate, f64> {
+    let mut nightly = measured_hours(log_lines);
+    nightly.extend(reported_hours(log_lines));
+    nightly
+}
+
+/// The running balance of sleep owed against `target` hours per night, one point per day with
+/// data.  Nights over target pay the debt down; the balance never goes below zero.
+pub fn sleep_debt(nightly: &BTreeMap<NaiveDate, f64>, target: f64) -> Vec<(NaiveDate, f64)> {
+    let mut debt = 0.0f64;
+    nightly
+        .iter()
+        .map(|(day, slept)| {
+            debt = (debt + target - slept).max(0.0);
+            (*day, debt)
+        })
+        .collect()
+}
+
+///////////////////////////////////////////// Regularity ///////////////////////////////////////////
+
+/// The Sleep Regularity Index (Phillips et al., 2017):  the likelihood of being in the same
+/// sleep/wake state at two time points 24 hours apart, scaled to -100..=100.  Only pairs of
+/// consecutive days that both have a reconstructed episode are compared.
+pub fn sleep_regularity_index(episodes: &[SleepEpisode]) -> Option<f64> {
+    let days = episodes
+        .iter()
+        .map(SleepEpisode::day)
+        .collect::<std::collections::BTreeSet<_>>();
+    let mut same = 0usize;
+    let mut total = 0usize;
+    for day in days.iter() {
+        let Some(next) = day.succ_opt() else {
+            continue;
+        };
+        if !days.contains(&next) {
+            continue;
+        }
+        // Each day runs from noon the day before to noon, so a whole night falls inside it.
+        let start = local_midnight(*day) - Duration::hours(12);
+        for step in 0..(24 * 60 / SRI_STEP_MINUTES) {
+            let when = start + Duration::minutes(step * SRI_STEP_MINUTES);
+            let later = when + Duration::hours(24);
+            let asleep = episodes.iter().any(|e| e.asleep_at(when));
+            let asleep_later = episodes.iter().any(|e| e.asleep_at(later));
+            if asleep == asleep_later {
+                same += 1;
+            }
+            total += 1;
+        }
+    }
+    if total == 0 {
+        return None;
+    }
+    Some(200.0 * same as f64 / total as f64 - 100.0)
+}
+
+/// Mean mid-sleep over the episodes, as hours relative to midnight.
+pub fn mean_mid_sleep<'a>(episodes: impl Iterator<Item = &'a SleepEpisode>) -> Option<f64> {
+    let mids = episodes.map(SleepEpisode::mid_sleep).collect::<Vec<_>>();
+    if mids.is_empty() {
+        None
+    } else {
+        Some(mids.iter().sum::<f64>() / mids.len() as f64)
+    }
+}
+
+/// Social jetlag:  how many hours mid-sleep on free days differs from mid-sleep on work days.
+pub fn social_jetlag(episodes: &[SleepEpisode]) -> Option<f64> {
+    let free = mean_mid_sleep(episodes.iter().filter(|e| e.is_free_day()))?;
+    let work = mean_mid_sleep(episodes.iter().filter(|e| !e.is_free_day()))?;
+    Some((free - work).abs())
+}
+
+//////////////////////////////////////////// SleepMetrics //////////////////////////////////////////
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct SleepMetrics {
+    pub since: NaiveDate,
+    pub until: NaiveDate,
+    pub nights: usize,
+    pub mean_hours: Option<f64>,
+    pub target_hours: f64,
+    pub debt_hours: f64,
+    pub sleep_regularity_index: Option<f64>,
+    pub mid_sleep: Option<f64>,
+    pub social_jetlag: Option<f64>,
+}
+
+impl SleepMetrics {
+    /// Compute the metrics over the days from `since` through `until`.  Debt starts from zero at
+    /// `since`.
+    pub fn compute(log_lines: &[LogLine], since: NaiveDate, until: NaiveDate, target: f64) -> Self {
+        let in_range = |day: &NaiveDate| since <= *day && *day <= until;
+        let nightly = nightly_hours(log_lines)
+            .into_iter()
+            .filter(|(day, _)| in_range(day))
+            .collect::<BTreeMap<_, _>>();
+        let episodes = episodes(log_lines)
+            .into_iter()
+            .filter(|episode| in_range(&episode.day()))
+            .collect::<Vec<_>>();
+        let mean_hours = if nightly.is_empty() {
+            None
+        } else {
+            Some(nightly.values().sum::<f64>() / nightly.len() as f64)
+        };
+        Self {
+            since,
+            until,
+            nights: nightly.len(),
+            mean_hours,
+            target_hours: target,
+            debt_hours: sleep_debt(&nightly, target)
+                .last()
+                .map(|(_, debt)| *debt)
+                .unwrap_or(0.0),
+            sleep_regularity_index: sleep_regularity_index(&episodes),
+            mid_sleep: mean_mid_sleep(episodes.iter()),
+            social_jetlag: social_jetlag(&episodes),
+        }
+    }
+}
+
+/// Render hours relative to midnight as a clock time.
+pub fn clock(hours: f64) -> String {
+    let minutes = (hours * 60.0).round() as i64;
+    let minutes = minutes.rem_euclid(24 * 60);
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+fn optional(value: Option<f64>, render: impl Fn(f64) -> String) -> String {
+    value.map(render).unwrap_or_else(|| "-".to_string())
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    arrrg_derive::CommandLine,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct SleepOptions {
+    #[arrrg(optional, "Transcript to read; defaults to $NOTAPSYCH_TRANSCRIPT.")]
+    pub transcript: String,
+    #[arrrg(
+        optional,
+        "First day to consider (YYYY-MM-DD); defaults to the first entry."
+    )]
+    pub since: String,
+    #[arrrg(optional, "Last day to consider (YYYY-MM-DD); defaults to today.")]
+    pub until: String,
+    #[arrrg(optional, "Nightly sleep target in hours; defaults to 8.")]
+    pub target: String,
+    #[arrrg(optional, "Output format: table or json.")]
+    pub format: String,
+}
+
+pub fn main(args: &[String]) {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (options, free) = SleepOptions::from_arguments_relaxed(
+        "USAGE: notapsych sleep [--since DAY] [--until DAY] [--target HOURS] [--format table|json]",
+        &args,
+    );
+    if !free.is_empty() {
+        eprintln!("command takes no positional arguments");
+        std::process::exit(13);
+    }
+    let target = if options.target.is_empty() {
+        8.0
+    } else {
+        options.target.parse::<f64>().unwrap_or_else(|err| {
+            eprintln!("--target should be a number of hours: {err}");
+            std::process::exit(13);
+        })
+    };
+    let log_lines = read_log_lines(&options.transcript);
+    let until = parse_day("--until", &options.until).unwrap_or_else(|| Local::now().date_naive());
+    let since = parse_day("--since", &options.since)
+        .or_else(|| log_lines.iter().filter_map(LogLine::day).min())
+        .unwrap_or(until);
+    // One row per calendar month so the metrics can be followed over time, then the whole range.
+    let mut rows = vec![];
+    let mut start = since;
+    while start <= until {
+        let next_month = if start.month() == 12 {
+            NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)
+        }
+        .expect("first of the month should always exist");
+        let end = next_month.pred_opt().unwrap_or(next_month).min(until);
+        rows.push(SleepMetrics::compute(&log_lines, start, end, target));
+        start = next_month;
+    }
+    rows.push(SleepMetrics::compute(&log_lines, since, until, target));
+    match options.format.as_str() {
+        "" | "table" => {
+            println!(
+                "{:<10}  {:<10}  {:>6} {:>6} {:>8} {:>6} {:>9} {:>8}",
+                "since", "until", "nights", "mean", "debt", "SRI", "mid-sleep", "jetlag"
+            );
+            for row in rows.iter() {
+                println!(
+                    "{:<10}  {:<10}  {:>6} {:>6} {:>8.1} {:>6} {:>9} {:>8}",
+                    row.since,
+                    row.until,
+                    row.nights,
+                    optional(row.mean_hours, |h| format!("{h:.2}")),
+                    row.debt_hours,
+                    optional(row.sleep_regularity_index, |sri| format!("{sri:.0}")),
+                    optional(row.mid_sleep, clock),
+                    optional(row.social_jetlag, |h| format!("{h:.2}h")),
+                );
+            }
+        }
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&rows).expect("metrics should always serialize")
+        ),
+        format => {
+            eprintln!("unknown format {format:?}; expected table or json");
+            std::process::exit(13);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2025, 6, day, hour, 0, 0)
+            .single()
+            .expect("June has no DST transitions")
+    }
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    fn slept(day: u32, hour: u32, hours: f64, source: Option<&str>, measured: bool) -> LogLine {
+        LogLine::HoursSlept {
+            recorded_at: at(day, hour).fixed_offset().to_rfc3339(),
+            sleep_hours: hours,
+            justification: String::new(),
+            imported_from: source.map(str::to_string),
+            measured,
+            provenance: vec![],
+        }
+    }
+
+    #[test]
+    fn one_value_per_night() {
+        let log_lines = vec![
+            slept(1, 7, 7.5, Some("fitbit"), true),
+            slept(1, 15, 1.0, Some("fitbit"), true),
+            slept(1, 8, 6.0, None, false),
+            slept(1, 9, 6.5, None, false),
+            slept(1, 9, 9.0, Some("sleep-cycle"), false),
+            slept(2, 7, 8.0, Some("fitbit"), true),
+        ];
+        assert_eq!(BTreeMap::from([(day(1), 6.5)]), reported_hours(&log_lines));
+        assert_eq!(
+            BTreeMap::from([(day(1), 7.5), (day(2), 8.0)]),
+            measured_hours(&log_lines)
+        );
+        assert_eq!(
+            BTreeMap::from([(day(1), 6.5), (day(2), 8.0)]),
+            nightly_hours(&log_lines)
+        );
+    }
+
+    #[test]
+    fn imported_diary_when_nothing_else() {
+        let log_lines = vec![slept(1, 9, 9.0, Some("sleep-cycle"), false)];
+        assert_eq!(BTreeMap::from([(day(1), 9.0)]), reported_hours(&log_lines));
+    }
+
+    #[test]
+    fn sleep_debt_accumulates_and_pays_down() {
+        let nightly = BTreeMap::from([(day(1), 6.0), (day(2), 7.0), (day(3), 10.0)]);
+        assert_eq!(
+            vec![(day(1), 2.0), (day(2), 3.0), (day(3), 1.0)],
+            sleep_debt(&nightly, 8.0)
+        );
+    }
+
+    #[test]
+    fn sleep_debt_never_negative() {
+        let nightly = BTreeMap::from([(day(1), 10.0), (day(2), 6.0)]);
+        assert_eq!(
+            vec![(day(1), 0.0), (day(2), 2.0)],
+            sleep_debt(&nightly, 8.0)
+        );
+    }
+
+    #[test]
+    fn sleep_regularity_index_regular() {
+        let episodes = vec![
+            SleepEpisode {
+                onset: at(9, 23),
+                wake: at(10, 7),
+            },
+            SleepEpisode {
+                onset: at(10, 23),
+                wake: at(11, 7),
+            },
+        ];
+        assert_eq!(Some(100.0), sleep_regularity_index(&episodes));
+    }
+
+    #[test]
+    fn sleep_regularity_index_shifted() {
+        // Asleep together 03:00-07:00 and awake together 11:00-23:00:  16 of 24 hours agree.
+        let episodes = vec![
+            SleepEpisode {
+                onset: at(9, 23),
+                wake: at(10, 7),
+            },
+            SleepEpisode {
+                onset: at(11, 3),
+                wake: at(11, 11),
+            },
+        ];
+        let sri = sleep_regularity_index(&episodes).unwrap();
+        assert!((sri - 100.0 / 3.0).abs() < 1e-9, "{sri}");
+    }
+
+    #[test]
+    fn sleep_regularity_index_needs_consecutive_days() {
+        let episodes = vec![
+            SleepEpisode {
+                onset: at(9, 23),
+                wake: at(10, 7),
+            },
+            SleepEpisode {
+                onset: at(11, 23),
+                wake: at(12, 7),
+            },
+        ];
+        assert_eq!(None, sleep_regularity_index(&episodes));
+    }
+
+    #[test]
+    fn social_jetlag_free_versus_work() {
+        // Thursday's mid-sleep is 03:00; Saturday's is 05:00.
+        let work = SleepEpisode {
+            onset: at(11, 23),
+            wake: at(12, 7),
+        };
+        let free = SleepEpisode {
+            onset: at(14, 1),
+            wake: at(14, 9),
+        };
+        assert!(!work.is_free_day());
+        assert!(free.is_free_day());
+        assert_eq!(3.0, work.mid_sleep());
+        assert_eq!(Some(2.0), social_jetlag(&[work.clone(), free]));
+        assert_eq!(None, social_jetlag(&[work]));
+    }
+}
@@ -1,4 +1,5 @@
 use arrrg::CommandLine;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate};
 
 use claudius::{
     Anthropic, ContentBlock, JsonSchema, KnownModel, MessageContentBlock, MessageCreateParams,
@@ -6,14 +7,49 @@ use claudius::{
     ToolChoice, ToolParam, ToolResultBlock, ToolUnionParam, ToolUseBlock,
 };
 
+pub mod hooks;
+pub mod ledger;
+pub mod noise;
+
+use ledger::{DailyCost, Ledger, LedgerEntry, PriceTable};
+use noise::{NoiseFilter, Repeated};
+
 #[derive(
     Clone, Debug, Eq, PartialEq, arrrg_derive::CommandLine, serde::Deserialize, serde::Serialize,
 )]
+#[serde(default)]
 pub struct StayFocusedOptions {
     #[arrrg(optional, "Which histfile to tail for context.")]
     pub histfile: String,
     #[arrrg(optional, "How many lines to tail and maintain from the histfile.")]
     pub tail: usize,
+    #[arrrg(optional, "Daily spend cap in US cents; 0 disables the cap.")]
+    pub daily_budget_cents: u64,
+    #[arrrg(
+        flag,
+        "Downgrade to a cheaper model instead of refusing once over budget."
+    )]
+    pub downgrade: bool,
+    #[arrrg(
+        optional,
+        "File of extra glob (or re:regex) patterns, one per line, for commands to ignore."
+    )]
+    pub ignore_file: String,
+    #[arrrg(
+        flag,
+        "Do not apply the built-in ignore patterns (ls, cd, clear, git status, ...)."
+    )]
+    pub no_builtin_ignores: bool,
+    #[arrrg(
+        optional,
+        "Call the model once this many minutes have passed since the last call."
+    )]
+    pub min_interval_minutes: u64,
+    #[arrrg(
+        optional,
+        "Call the model once this many meaningful commands have run since the last call."
+    )]
+    pub min_new_commands: usize,
 }
 
 impl Default for StayFocusedOptions {
@@ -21,20 +57,83 @@ impl Default for StayFocusedOptions {
         Self {
             histfile: ".histfile".to_string(),
             tail: 10,
+            daily_budget_cents: 0,
+            downgrade: false,
+            ignore_file: String::new(),
+            no_builtin_ignores: false,
+            min_interval_minutes: 5,
+            min_new_commands: 3,
         }
     }
 }
 
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    arrrg_derive::CommandLine,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct UsageOptions {
+    #[arrrg(
+        optional,
+        "Report usage on or after this day (YYYY-MM-DD); defaults to a week ago."
+    )]
+    pub since: String,
+}
+
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct History {
-    pub tail: Vec<String>,
+    pub tail: Vec<Repeated>,
     pub last_index: usize,
     pub primary_objective: Option<String>,
     pub side_quests: Option<Vec<String>>,
     pub options: StayFocusedOptions,
+    #[serde(default)]
+    pub pending_commands: usize,
+    #[serde(default)]
+    pub last_called_at: Option<String>,
 }
 
 impl History {
+    /// True when the model should be called now.
+    pub fn should_call(&self) -> bool {
+        self.should_call_at(Local::now().fixed_offset())
+    }
+
+    /// The model is called at most every `min_new_commands` commands or `min_interval_minutes`
+    /// minutes, whichever comes first:  once enough commands have accumulated, or once enough
+    /// time has passed and there is at least one new command to look at.  The thresholds are the
+    /// ones saved with the history, so a hook invoked with different flags debounces the same.
+    pub fn should_call_at(&self, now: DateTime<FixedOffset>) -> bool {
+        if self.pending_commands == 0 {
+            return false;
+        }
+        if self.pending_commands >= self.options.min_new_commands {
+            return true;
+        }
+        let Some(last_called_at) = self.last_called_at.as_ref() else {
+            return true;
+        };
+        let Ok(last_called_at) = DateTime::parse_from_rfc3339(last_called_at) else {
+            return true;
+        };
+        let elapsed = now - last_called_at;
+        elapsed.num_minutes() >= self.options.min_interval_minutes as i64
+    }
+
+    /// The tail as the model sees it, one command per line.
+    pub fn tail_text(&self) -> String {
+        self.tail
+            .iter()
+            .map(Repeated::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     pub fn as_content_block(&self) -> MessageContentBlock {
         let mut objectives = String::new();
         if let Some(po) = self.primary_objective.as_ref() {
@@ -55,7 +154,7 @@ impl History {
         let histfile = "<histfile>
 "
         .to_string()
-            + &self.tail.join("\n")
+            + &self.tail_text()
             + "
 </histfile>
 ";
@@ -98,7 +197,87 @@ async fn process_tool_call(tool_use: &ToolUseBlock, history: &mut History) -> St
     }
 }
 
+/// Pick the model to call given what has been `spent` today, or None if the budget is exhausted.
+fn budgeted_model(options: &StayFocusedOptions, spent: &DailyCost) -> Option<Model> {
+    let model = Model::Known(KnownModel::Claude37SonnetLatest);
+    if options.daily_budget_cents == 0 {
+        return Some(model);
+    }
+    for model in spent.unpriced.iter() {
+        eprintln!("{model} is not in the price table; its usage does not count toward the budget");
+    }
+    let spent = spent.dollars;
+    if spent * 100.0 < options.daily_budget_cents as f64 {
+        Some(model)
+    } else if options.downgrade {
+        eprintln!("spent ${spent:.2} today; downgrading model");
+        Some(Model::Known(KnownModel::Claude35HaikuLatest))
+    } else {
+        eprintln!("spent ${spent:.2} today; over the daily budget, not calling the model");
+        None
+    }
+}
+
+fn usage(args: &[String]) {
+    let state_path = std::env::var("STAYFOCUSED_STATE").unwrap_or_else(|_| {
+        eprintln!("You should set STAYFOCUSED_STATE in your environment.");
+        std::process::exit(13);
+    });
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (options, free) = UsageOptions::from_arguments_relaxed(
+        "USAGE: stayfocused usage [--since YYYY-MM-DD]",
+        &args,
+    );
+    if !free.is_empty() {
+        eprintln!("command takes no positional arguments");
+        std::process::exit(13);
+    }
+    let since = if options.since.is_empty() {
+        Local::now().date_naive() - chrono::Days::new(7)
+    } else {
+        NaiveDate::parse_from_str(&options.since, "%Y-%m-%d").unwrap_or_else(|err| {
+            eprintln!("--since should be YYYY-MM-DD: {err}");
+            std::process::exit(13);
+        })
+    };
+    let ledger = Ledger::from_env(&state_path);
+    let prices = PriceTable::from_env();
+    let mut total = 0.0;
+    let mut unpriced = std::collections::BTreeSet::new();
+    for (day, cost) in ledger.daily_costs(&prices, since) {
+        if cost.unpriced.is_empty() {
+            println!("{day}  ${:>8.4}", cost.dollars);
+        } else {
+            let models = cost.unpriced.iter().cloned().collect::<Vec<_>>();
+            println!(
+                "{day}  ${:>8.4}  unpriced: {}",
+                cost.dollars,
+                models.join(", ")
+            );
+        }
+        total += cost.dollars;
+        unpriced.extend(cost.unpriced);
+    }
+    if unpriced.is_empty() {
+        println!("total       ${total:>8.4}");
+    } else {
+        println!("total       ${total:>8.4}  plus unpriced usage");
+    }
+}
+
 pub async fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    match args.get(1).map(String::as_str) {
+        Some("usage") => {
+            usage(&args[2..]);
+            return;
+        }
+        Some("init") => {
+            hooks::init(&args[2..]);
+            return;
+        }
+        _ => {}
+    }
     let state_path = std::env::var("STAYFOCUSED_STATE").unwrap_or_else(|_| {
         eprintln!("You should set STAYFOCUSED_STATE in your environment.");
         std::process::exit(13);
@@ -115,12 +294,6 @@ pub async fn main() {
             options.histfile
         )
     });
-    let mut actions = actions
-        .split_terminator('\n')
-        .rev()
-        .take(options.tail)
-        .collect::<Vec<_>>();
-    actions.reverse();
     let history = std::fs::read_to_string(&state_path).unwrap_or_else(|err| {
         if err.kind() == std::io::ErrorKind::NotFound {
             let default = History {
@@ -129,6 +302,8 @@ pub async fn main() {
                 primary_objective: None,
                 side_quests: None,
                 options: options.clone(),
+                pending_commands: 0,
+                last_called_at: None,
             };
             serde_json::to_string(&default).expect("all types serializable, no error")
         } else {
@@ -136,25 +311,57 @@ pub async fn main() {
         }
     });
     let mut history: History = serde_json::from_str(&history).expect("history should be JSON");
-    history.tail.extend(actions.into_iter().map(String::from));
+    let lines = actions.split_terminator('\n').collect::<Vec<_>>();
+    if lines.len() < history.last_index {
+        // The histfile was truncated or rotated; start over from its beginning.
+        history.last_index = 0;
+    }
+    let filter = NoiseFilter::new(!options.no_builtin_ignores, &options.ignore_file);
+    let actions = lines[history.last_index..]
+        .iter()
+        .map(|line| noise::strip_timestamp(line))
+        .filter(|action| !filter.is_noise(action))
+        .map(Repeated::once)
+        .collect::<Vec<_>>();
+    history.last_index = lines.len();
+    history.pending_commands += actions.len();
+    let actions = noise::collapse(&actions);
+    let actions = &actions[actions.len().saturating_sub(options.tail)..];
+    history.tail.extend(actions.iter().cloned());
+    history.tail = noise::collapse(&history.tail);
     history.tail = history
         .tail
         .split_off(history.tail.len().saturating_sub(history.options.tail));
+    if !history.should_call() {
+        save_history(&state_path, &history);
+        return;
+    }
+    history.pending_commands = 0;
+    history.last_called_at = Some(Local::now().fixed_offset().to_rfc3339());
+    // Hooks run in the background and overlap; record the call before making it so runs that
+    // start while it is in flight are debounced instead of calling the model again.
+    save_history(&state_path, &history);
 
+    let ledger = Ledger::from_env(&state_path);
+    let prices = PriceTable::from_env();
     let client = Anthropic::new(None).expect("Should be able to instantiate an Anthropic client");
     let message = MessageParam::new(
         MessageParamContent::String(
-            "<histfile>\n".to_string() + &history.tail.join("\n") + "\n</histfile>",
+            "<histfile>\n".to_string() + &history.tail_text() + "\n</histfile>",
         ),
         MessageRole::User,
     );
     let mut messages = vec![message];
 
     for _ in 0..3 {
+        let Some(model) = budgeted_model(&options, &ledger.spent_today(&prices)) else {
+            break;
+        };
+        let model_used = model.to_string();
         let params = MessageCreateParams {
             max_tokens: 1000,
             messages: messages.clone(),
-            model: Model::Known(KnownModel::Claude37SonnetLatest),
+            model,
             system: Some(SystemPrompt::String(
                 include_str!("stayfocused.md").to_string(),
             )),
@@ -192,6 +399,22 @@ pub async fn main() {
 
         let response = client.send(params).await.unwrap();
         println!("{response:?}");
+        ledger.append(&LedgerEntry {
+            recorded_at: Local::now().fixed_offset().to_rfc3339(),
+            model: model_used,
+            input_tokens: u64::try_from(response.usage.input_tokens).unwrap_or(0),
+            output_tokens: u64::try_from(response.usage.output_tokens).unwrap_or(0),
+            cache_creation_input_tokens: response
+                .usage
+                .cache_creation_input_tokens
+                .map(|tokens| u64::try_from(tokens).unwrap_or(0))
+                .unwrap_or(0),
+            cache_read_input_tokens: response
+                .usage
+                .cache_read_input_tokens
+                .map(|tokens| u64::try_from(tokens).unwrap_or(0))
+                .unwrap_or(0),
+        });
 
         // Add assistant response to messages
         let assistant_content: Vec<MessageContentBlock> = response
@@ -244,7 +467,109 @@ pub async fn main() {
         }
     }
 
-    // Save updated history
-    let history_json = serde_json::to_string(&history).expect("history should serialize");
-    std::fs::write(&state_path, history_json).expect("should be able to write state file");
+    save_history(&state_path, &history);
+}
+
+/// Write the state file by renaming a complete copy over it, so a concurrent run never reads a
+/// partial write.
+fn save_history(state_path: &str, history: &History) {
+    let history_json = serde_json::to_string(history).expect("history should serialize");
+    let tmp_path = format!("{state_path}.{}.tmp", std::process::id());
+    std::fs::write(&tmp_path, history_json).expect("should be able to write state file");
+    std::fs::rename(&tmp_path, state_path).expect("should be able to replace state file");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spent(dollars: f64) -> DailyCost {
+        DailyCost {
+            dollars,
+            ..DailyCost::default()
+        }
+    }
+
+    fn sonnet() -> Option<Model> {
+        Some(Model::Known(KnownModel::Claude37SonnetLatest))
+    }
+
+    fn history(pending_commands: usize, last_called_at: Option<&str>) -> History {
+        History {
+            tail: vec![],
+            last_index: 0,
+            primary_objective: None,
+            side_quests: None,
+            options: StayFocusedOptions::default(),
+            pending_commands,
+            last_called_at: last_called_at.map(String::from),
+        }
+    }
+
+    fn at(when: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(when).unwrap()
+    }
+
+    #[test]
+    fn should_call_needs_a_new_command() {
+        let history = history(0, None);
+        assert!(!history.should_call_at(at("2025-06-10T12:00:00-07:00")));
+    }
+
+    #[test]
+    fn should_call_on_first_run() {
+        let history = history(1, None);
+        assert!(history.should_call_at(at("2025-06-10T12:00:00-07:00")));
+    }
+
+    #[test]
+    fn should_call_after_enough_commands() {
+        let history = history(3, Some("2025-06-10T12:00:00-07:00"));
+        assert!(history.should_call_at(at("2025-06-10T12:01:00-07:00")));
+    }
+
+    #[test]
+    fn should_call_after_enough_minutes() {
+        let history = history(1, Some("2025-06-10T12:00:00-07:00"));
+        assert!(!history.should_call_at(at("2025-06-10T12:04:59-07:00")));
+        assert!(history.should_call_at(at("2025-06-10T12:05:00-07:00")));
+    }
+
+    #[test]
+    fn should_call_uses_the_saved_options() {
+        let mut history = history(2, Some("2025-06-10T12:00:00-07:00"));
+        let now = at("2025-06-10T12:01:00-07:00");
+        assert!(!history.should_call_at(now));
+        history.options.min_new_commands = 2;
+        assert!(history.should_call_at(now));
+    }
+
+    #[test]
+    fn budgeted_model_within_budget() {
+        let options = StayFocusedOptions {
+            daily_budget_cents: 100,
+            ..StayFocusedOptions::default()
+        };
+        assert_eq!(sonnet(), budgeted_model(&options, &spent(0.99)));
+    }
+
+    #[test]
+    fn budgeted_model_without_a_budget() {
+        let options = StayFocusedOptions::default();
+        assert_eq!(sonnet(), budgeted_model(&options, &spent(1000.0)));
+    }
+
+    #[test]
+    fn budgeted_model_over_budget() {
+        let mut options = StayFocusedOptions {
+            daily_budget_cents: 100,
+            ..StayFocusedOptions::default()
+        };
+        assert_eq!(None, budgeted_model(&options, &spent(1.0)));
+        options.downgrade = true;
+        assert_eq!(
+            Some(Model::Known(KnownModel::Claude35HaikuLatest)),
+            budgeted_model(&options, &spent(1.0))
+        );
+    }
 }
@@ -0,0 +1,46 @@
+const ZSH: &str = r#"# stayfocused: add to ~/.zshrc with `eval "$(stayfocused init zsh)"`.
+# Without INC_APPEND_HISTORY zsh only writes the histfile on exit.
+setopt INC_APPEND_HISTORY
+_stayfocused_precmd() {
+    ( "{STAYFOCUSED}" --histfile "${HISTFILE:-$HOME/.histfile}" >/dev/null 2>&1 & )
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook precmd _stayfocused_precmd
+"#;
+
+const BASH: &str = r#"# stayfocused: add to ~/.bashrc with `eval "$(stayfocused init bash)"`.
+_stayfocused_prompt_command() {
+    history -a
+    ( "{STAYFOCUSED}" --histfile "${HISTFILE:-$HOME/.bash_history}" >/dev/null 2>&1 & )
+}
+PROMPT_COMMAND="_stayfocused_prompt_command${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+"#;
+
+const FISH: &str = r#"# stayfocused: add to config.fish with `stayfocused init fish | source`.
+# fish's own history is not line-oriented, so keep a plain histfile alongside it.
+set -g __stayfocused_histfile "$HOME/.stayfocused_fish_history"
+function __stayfocused_postexec --on-event fish_postexec
+    string join ' ' -- $argv >> $__stayfocused_histfile
+    "{STAYFOCUSED}" --histfile $__stayfocused_histfile >/dev/null 2>&1 &
+    disown
+end
+"#;
+
+/// Print the hook snippet for the shell named in `args`.  Debouncing happens inside stayfocused
+/// itself, so the hooks are free to run it after every command.
+pub fn init(args: &[String]) {
+    let snippet = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["zsh"] => ZSH,
+        ["bash"] => BASH,
+        ["fish"] => FISH,
+        _ => {
+            eprintln!("USAGE: stayfocused init zsh|bash|fish");
+            std::process::exit(13);
+        }
+    };
+    let exe = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.to_str().map(String::from))
+        .unwrap_or_else(|| "stayfocused".to_string());
+    print!("{}", snippet.replace("{STAYFOCUSED}", &exe));
+}
@@ -0,0 +1,287 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate};
+
+/////////////////////////////////////////////// Price //////////////////////////////////////////////
+
+/// Dollars per million tokens for one model.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+    pub cache_write: f64,
+    pub cache_read: f64,
+}
+
+///////////////////////////////////////////// PriceTable ///////////////////////////////////////////
+
+/// Prices keyed by model name.  Models missing from the table have no cost; daily totals name
+/// them rather than count them as free, so an out-of-date table is noticed instead of silently
+/// under-reporting.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct PriceTable {
+    pub models: BTreeMap<String, Price>,
+}
+
+impl PriceTable {
+    /// Load the built-in prices, overridden model-by-model by the JSON object found at
+    /// `STAYFOCUSED_PRICES`, if set.
+    pub fn from_env() -> Self {
+        let mut table = Self::default();
+        if let Ok(path) = std::env::var("STAYFOCUSED_PRICES") {
+            let prices = std::fs::read_to_string(&path)
+                .unwrap_or_else(|err| panic!("could not read price table from {path}: {err}"));
+            let overrides: BTreeMap<String, Price> = serde_json::from_str(&prices)
+                .unwrap_or_else(|err| panic!("price table at {path} should be JSON: {err}"));
+            table.models.extend(overrides);
+        }
+        table
+    }
+
+    pub fn price(&self, model: &str) -> Option<&Price> {
+        self.models.get(model)
+    }
+
+    /// The cost of `entry` in dollars, or None when its model is not in the table.
+    pub fn cost(&self, entry: &LedgerEntry) -> Option<f64> {
+        let price = self.price(&entry.model)?;
+        Some(
+            (entry.input_tokens as f64 * price.input
+                + entry.output_tokens as f64 * price.output
+                + entry.cache_creation_input_tokens as f64 * price.cache_write
+                + entry.cache_read_input_tokens as f64 * price.cache_read)
+                / 1_000_000.0,
+        )
+    }
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        let mut models = BTreeMap::new();
+        models.insert(
+            "claude-3-7-sonnet-latest".to_string(),
+            Price {
+                input: 3.0,
+                output: 15.0,
+                cache_write: 3.75,
+                cache_read: 0.30,
+            },
+        );
+        models.insert(
+            "claude-3-5-haiku-latest".to_string(),
+            Price {
+                input: 0.80,
+                output: 4.0,
+                cache_write: 1.0,
+                cache_read: 0.08,
+            },
+        );
+        Self { models }
+    }
+}
+
+///////////////////////////////////////////// LedgerEntry //////////////////////////////////////////
+
+/// The token usage of a single response.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct LedgerEntry {
+    pub recorded_at: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+impl LedgerEntry {
+    pub fn recorded_at(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.recorded_at).ok()
+    }
+
+    /// The local calendar day this entry counts against.
+    pub fn day(&self) -> Option<NaiveDate> {
+        self.recorded_at()
+            .map(|when| when.with_timezone(&Local).date_naive())
+    }
+}
+
+////////////////////////////////////////////// DailyCost ///////////////////////////////////////////
+
+/// What was spent on one day:  the dollars for priced responses, and the models of any responses
+/// that could not be priced.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DailyCost {
+    pub dollars: f64,
+    pub unpriced: BTreeSet<String>,
+}
+
+/// Cost per local day of every entry on or after `since`.
+pub fn daily_costs(
+    entries: &[LedgerEntry],
+    prices: &PriceTable,
+    since: NaiveDate,
+) -> BTreeMap<NaiveDate, DailyCost> {
+    let mut costs: BTreeMap<NaiveDate, DailyCost> = BTreeMap::new();
+    for entry in entries {
+        let Some(day) = entry.day() else {
+            continue;
+        };
+        if day < since {
+            continue;
+        }
+        let cost = costs.entry(day).or_default();
+        match prices.cost(entry) {
+            Some(dollars) => cost.dollars += dollars,
+            None => {
+                cost.unpriced.insert(entry.model.clone());
+            }
+        }
+    }
+    costs
+}
+
+/////////////////////////////////////////////// Ledger /////////////////////////////////////////////
+
+/// An append-only JSONL file of [LedgerEntry].
+pub struct Ledger {
+    path: String,
+}
+
+impl Ledger {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The ledger lives at `STAYFOCUSED_LEDGER`, or next to the state file when that is unset.
+    pub fn from_env(state_path: &str) -> Self {
+        match std::env::var("STAYFOCUSED_LEDGER") {
+            Ok(path) => Self::new(path),
+            Err(_) => Self::new(format!("{state_path}.ledger")),
+        }
+    }
+
+    pub fn append(&self, entry: &LedgerEntry) {
+        let mut log = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .unwrap_or_else(|err| panic!("could not open ledger {} for append: {err}", self.path));
+        log.write_all(
+            (serde_json::to_string(entry).expect("ledger entry should always serialize") + "\n")
+                .as_bytes(),
+        )
+        .expect("could not append to ledger; it may be corrupt");
+    }
+
+    /// Every entry in the ledger.  A missing ledger is empty; lines that fail to parse are
+    /// skipped so a torn write never blocks the hook.
+    pub fn entries(&self) -> Vec<LedgerEntry> {
+        let ledger = match std::fs::read_to_string(&self.path) {
+            Ok(ledger) => ledger,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return vec![];
+            }
+            Err(err) => panic!("could not read ledger from {}: {err}", self.path),
+        };
+        ledger
+            .split_terminator('\n')
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    /// Cost per local day for every day on or after `since`.
+    pub fn daily_costs(
+        &self,
+        prices: &PriceTable,
+        since: NaiveDate,
+    ) -> BTreeMap<NaiveDate, DailyCost> {
+        daily_costs(&self.entries(), prices, since)
+    }
+
+    /// What has been spent so far today.
+    pub fn spent_today(&self, prices: &PriceTable) -> DailyCost {
+        let today = Local::now().date_naive();
+        self.daily_costs(prices, today)
+            .remove(&today)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(recorded_at: &str, model: &str, input: u64, output: u64) -> LedgerEntry {
+        LedgerEntry {
+            recorded_at: recorded_at.to_string(),
+            model: model.to_string(),
+            input_tokens: input,
+            output_tokens: output,
+            cache_creation_input_tokens: 0,
+            cache_read_input_tokens: 0,
+        }
+    }
+
+    fn day(when: &str) -> NaiveDate {
+        entry(when, "", 0, 0).day().unwrap()
+    }
+
+    #[test]
+    fn cost_is_per_million_tokens() {
+        let prices = PriceTable::default();
+        let mut sonnet = entry(
+            "2025-06-10T12:00:00Z",
+            "claude-3-7-sonnet-latest",
+            1_000_000,
+            0,
+        );
+        assert_eq!(Some(3.0), prices.cost(&sonnet));
+        sonnet.output_tokens = 100_000;
+        sonnet.cache_creation_input_tokens = 1_000_000;
+        sonnet.cache_read_input_tokens = 1_000_000;
+        let cost = prices.cost(&sonnet).unwrap();
+        assert!((cost - (3.0 + 1.5 + 3.75 + 0.30)).abs() < 1e-9, "{cost}");
+    }
+
+    #[test]
+    fn unpriced_models_have_no_cost() {
+        let prices = PriceTable::default();
+        let entry = entry("2025-06-10T12:00:00Z", "claude-next", 1_000_000, 0);
+        assert_eq!(None, prices.cost(&entry));
+    }
+
+    #[test]
+    fn daily_costs_total_by_day_and_name_unpriced_models() {
+        let prices = PriceTable::default();
+        let entries = vec![
+            entry(
+                "2025-06-09T12:00:00Z",
+                "claude-3-7-sonnet-latest",
+                1_000_000,
+                0,
+            ),
+            entry(
+                "2025-06-10T12:00:00Z",
+                "claude-3-7-sonnet-latest",
+                1_000_000,
+                0,
+            ),
+            entry(
+                "2025-06-10T12:05:00Z",
+                "claude-3-5-haiku-latest",
+                0,
+                1_000_000,
+            ),
+            entry("2025-06-10T12:10:00Z", "claude-next", 1_000_000, 0),
+            entry("not a time", "claude-3-7-sonnet-latest", 1_000_000, 0),
+        ];
+        let since = day("2025-06-10T12:00:00Z");
+        let costs = daily_costs(&entries, &prices, since);
+        assert_eq!(1, costs.len());
+        let cost = &costs[&since];
+        assert!((cost.dollars - 7.0).abs() < 1e-9, "{}", cost.dollars);
+        assert_eq!(BTreeSet::from(["claude-next".to_string()]), cost.unpriced);
+    }
+}
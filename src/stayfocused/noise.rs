@@ -0,0 +1,323 @@
+use regex::Regex;
+
+/// Commands that say nothing about what the user is working on.
+pub const BUILTIN_IGNORES: &[&str] = &[
+    "ls",
+    "ls *",
+    "ll",
+    "ll *",
+    "la",
+    "la *",
+    "l",
+    "cd",
+    "cd *",
+    "pwd",
+    "clear",
+    "reset",
+    "exit",
+    "history",
+    "history *",
+    "git status",
+    "git st",
+    "git diff",
+    "git log",
+    "git log *",
+    "fg",
+    "bg",
+    "jobs",
+    "..",
+    "...",
+];
+
+///////////////////////////////////////////// NoiseFilter //////////////////////////////////////////
+
+/// Prefix that marks a line of the ignore file as a regular expression rather than a glob.
+pub const REGEX_PREFIX: &str = "re:";
+
+/// One ignore pattern:  a glob that must match the whole command, or a regex that may match any
+/// part of it unless anchored.
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Glob(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    /// Parse one line of an ignore file.  Lines starting with `re:` are regexes.
+    pub fn parse(line: &str) -> Result<Self, regex::Error> {
+        match line.strip_prefix(REGEX_PREFIX) {
+            Some(re) => Ok(Pattern::Regex(Regex::new(re.trim())?)),
+            None => Ok(Pattern::Glob(line.to_string())),
+        }
+    }
+
+    pub fn is_match(&self, command: &str) -> bool {
+        match self {
+            Pattern::Glob(glob) => glob_match(glob, command),
+            Pattern::Regex(re) => re.is_match(command),
+        }
+    }
+}
+
+/// Decides which histfile lines are noise and should never reach the model.
+#[derive(Clone, Debug, Default)]
+pub struct NoiseFilter {
+    patterns: Vec<Pattern>,
+}
+
+impl NoiseFilter {
+    /// Build a filter from the built-in patterns (unless disabled) and the patterns found
+    /// one-per-line in `ignore_file`.  Lines are globs, or regexes when prefixed with `re:`.
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn new(builtin: bool, ignore_file: &str) -> Self {
+        let mut patterns = vec![];
+        if builtin {
+            patterns.extend(BUILTIN_IGNORES.iter().map(|p| Pattern::Glob(p.to_string())));
+        }
+        if !ignore_file.is_empty() {
+            let ignores = std::fs::read_to_string(ignore_file).unwrap_or_else(|err| {
+                panic!("could not read ignore patterns from {ignore_file}: {err}")
+            });
+            patterns.extend(
+                ignores
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(|line| {
+                        Pattern::parse(line).unwrap_or_else(|err| {
+                            panic!("bad ignore pattern {line:?} in {ignore_file}: {err}")
+                        })
+                    }),
+            );
+        }
+        Self { patterns }
+    }
+
+    pub fn is_noise(&self, command: &str) -> bool {
+        let command = command.trim();
+        command.is_empty()
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.is_match(command))
+    }
+}
+
+/// Match `text` against a shell-style glob where `*` matches any run of characters and `?`
+/// matches exactly one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Strip the `: <timestamp>:<duration>;` prefix zsh writes with EXTENDED_HISTORY.  The
+/// `#<timestamp>` lines bash writes with HISTTIMEFORMAT are not commands and become empty.
+pub fn strip_timestamp(line: &str) -> &str {
+    if let Some(stamp) = line.strip_prefix('#') {
+        if !stamp.is_empty() && stamp.chars().all(|c| c.is_ascii_digit()) {
+            return "";
+        }
+    }
+    if let Some(rest) = line.strip_prefix(": ") {
+        if let Some((stamp, command)) = rest.split_once(';') {
+            if stamp.chars().all(|c| c.is_ascii_digit() || c == ':') {
+                return command;
+            }
+        }
+    }
+    line
+}
+
+////////////////////////////////////////////// Repeated //////////////////////////////////////////
+
+/// A command and how many times in a row it ran.  Shown to the model as `cmd (xN)`.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(from = "RepeatedRepr")]
+pub struct Repeated {
+    pub command: String,
+    pub count: usize,
+}
+
+impl Repeated {
+    pub fn once(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            count: 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Repeated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.count > 1 {
+            write!(f, "{} (x{})", self.command, self.count)
+        } else {
+            write!(f, "{}", self.command)
+        }
+    }
+}
+
+/// State files written before counts were tracked hold plain strings.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum RepeatedRepr {
+    Command(String),
+    Repeated { command: String, count: usize },
+}
+
+impl From<RepeatedRepr> for Repeated {
+    fn from(repr: RepeatedRepr) -> Self {
+        match repr {
+            RepeatedRepr::Command(command) => Repeated::once(command),
+            RepeatedRepr::Repeated { command, count } => Repeated { command, count },
+        }
+    }
+}
+
+/// Collapse consecutive duplicates into a single entry.  Entries that were already collapsed
+/// keep accumulating.
+pub fn collapse(entries: &[Repeated]) -> Vec<Repeated> {
+    let mut collapsed: Vec<Repeated> = vec![];
+    for entry in entries {
+        match collapsed.last_mut() {
+            Some(last) if last.command == entry.command => {
+                last.count += entry.count;
+            }
+            _ => {
+                collapsed.push(entry.clone());
+            }
+        }
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn once(entries: &[&str]) -> Vec<Repeated> {
+        entries.iter().map(|e| Repeated::once(*e)).collect()
+    }
+
+    fn repeated(command: &str, count: usize) -> Repeated {
+        Repeated {
+            command: command.to_string(),
+            count,
+        }
+    }
+
+    #[test]
+    fn glob_match_literal() {
+        assert!(glob_match("git status", "git status"));
+        assert!(!glob_match("git status", "git status -s"));
+        assert!(!glob_match("ls", "lsof"));
+    }
+
+    #[test]
+    fn glob_match_wildcards() {
+        assert!(glob_match("ls *", "ls -la"));
+        assert!(!glob_match("ls *", "ls"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("git log *", "git log --oneline -n 5"));
+        assert!(glob_match("c?", "cd"));
+        assert!(!glob_match("c?", "c"));
+        assert!(glob_match("*test*", "cargo test --workspace"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXbYbZ"));
+    }
+
+    #[test]
+    fn noise_filter_builtins() {
+        let filter = NoiseFilter::new(true, "");
+        assert!(filter.is_noise("ls -la"));
+        assert!(filter.is_noise("  "));
+        assert!(!filter.is_noise("cargo build"));
+        assert!(!NoiseFilter::new(false, "").is_noise("ls -la"));
+    }
+
+    #[test]
+    fn noise_filter_regex() {
+        let filter = NoiseFilter {
+            patterns: vec![
+                Pattern::parse("re:^kubectl (get|describe) ").unwrap(),
+                Pattern::parse("make").unwrap(),
+            ],
+        };
+        assert!(filter.is_noise("kubectl get pods"));
+        assert!(!filter.is_noise("kubectl apply -f x.yaml"));
+        assert!(filter.is_noise("make"));
+        assert!(!filter.is_noise("make test"));
+        assert!(Pattern::parse("re:(").is_err());
+    }
+
+    #[test]
+    fn strip_timestamp_zsh() {
+        assert_eq!("cargo build", strip_timestamp(": 1700000000:0;cargo build"));
+        assert_eq!(": not a stamp;x", strip_timestamp(": not a stamp;x"));
+        assert_eq!("cargo build", strip_timestamp("cargo build"));
+    }
+
+    #[test]
+    fn strip_timestamp_bash() {
+        assert_eq!("", strip_timestamp("#1700000000"));
+        assert_eq!("# a comment", strip_timestamp("# a comment"));
+        assert_eq!("#", strip_timestamp("#"));
+    }
+
+    #[test]
+    fn collapse_consecutive() {
+        assert_eq!(
+            vec![
+                repeated("make", 1),
+                repeated("vim x", 2),
+                repeated("make", 1)
+            ],
+            collapse(&once(&["make", "vim x", "vim x", "make"]))
+        );
+    }
+
+    #[test]
+    fn collapse_accumulates() {
+        assert_eq!(
+            vec![repeated("make", 5)],
+            collapse(&[
+                repeated("make", 3),
+                repeated("make", 1),
+                repeated("make", 1)
+            ])
+        );
+        assert_eq!(Vec::<Repeated>::new(), collapse(&[]));
+    }
+
+    #[test]
+    fn collapse_leaves_commands_that_look_counted() {
+        let entries = once(&["echo done (x2)", "echo done (x2)"]);
+        assert_eq!(vec![repeated("echo done (x2)", 2)], collapse(&entries));
+        assert_eq!("echo done (x2) (x2)", collapse(&entries)[0].to_string());
+    }
+
+    #[test]
+    fn repeated_reads_old_state() {
+        let tail: Vec<Repeated> =
+            serde_json::from_str(r#"["make", {"command": "vim x", "count": 2}]"#).unwrap();
+        assert_eq!(vec![repeated("make", 1), repeated("vim x", 2)], tail);
+    }
+}
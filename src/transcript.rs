@@ -0,0 +1,1223 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate};
+
+/// The version of the transcript format this build writes.  Bump it, and publish a new schema,
+/// whenever a change to [LogLine] would make old readers misinterpret new lines.
+pub const SCHEMA_VERSION: u32 = 1;
+
+////////////////////////////////////////////// LogLine /////////////////////////////////////////////
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(tag = "type")]
+pub enum LogLine {
+    #[serde(rename = "last-slept")]
+    LastSlept {
+        recorded_at: String,
+        awake_hours: f64,
+        justification: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        imported_from: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        measured: bool,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+    #[serde(rename = "hours-slept")]
+    HoursSlept {
+        recorded_at: String,
+        sleep_hours: f64,
+        justification: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        imported_from: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        measured: bool,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+    #[serde(rename = "sleep-quality")]
+    SleepQuality {
+        recorded_at: String,
+        answer: f64,
+        justification: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        imported_from: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        measured: bool,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+    #[serde(rename = "medication")]
+    Medication {
+        recorded_at: String,
+        substance: String,
+        dose: Dose,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reported: Option<Reported>,
+        justification: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+    #[serde(rename = "hygiene")]
+    Hygiene {
+        recorded_at: String,
+        hygiene: String,
+        justification: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+    #[serde(rename = "alcohol")]
+    Alcohol {
+        recorded_at: String,
+        standard_drinks: f64,
+        started_at: String,
+        duration_hours: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reported: Option<Reported>,
+        justification: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+    #[serde(rename = "mood")]
+    Mood {
+        recorded_at: String,
+        valence: f64,
+        arousal: f64,
+        anxiety: f64,
+        irritability: f64,
+        #[serde(default)]
+        emotions: Vec<String>,
+        justification: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+    #[serde(rename = "exercise")]
+    Exercise {
+        recorded_at: String,
+        activity: String,
+        duration_minutes: f64,
+        intensity: String,
+        justification: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+    #[serde(rename = "meals")]
+    Meals {
+        recorded_at: String,
+        meals: f64,
+        skipped: f64,
+        regular: bool,
+        justification: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+    #[serde(rename = "hydration")]
+    Hydration {
+        recorded_at: String,
+        water_ml: f64,
+        justification: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+    #[serde(rename = "journal")]
+    Journal {
+        recorded_at: String,
+        text: String,
+        #[serde(default)]
+        topics: Vec<String>,
+        /// From -1 to 1, or absent when it could not be extracted.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sentiment: Option<f64>,
+        #[serde(default)]
+        people: Vec<String>,
+        #[serde(default)]
+        signals: Vec<Signal>,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        justification: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+    #[serde(rename = "screening")]
+    Screening {
+        recorded_at: String,
+        instrument: String,
+        responses: Vec<u8>,
+        total: u32,
+        severity: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        justification: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        provenance: Vec<Provenance>,
+    },
+}
+
+impl LogLine {
+    /// Every value of the `type` tag this version understands.
+    pub const KINDS: &'static [&'static str] = &[
+        "last-slept",
+        "hours-slept",
+        "sleep-quality",
+        "medication",
+        "hygiene",
+        "alcohol",
+        "mood",
+        "exercise",
+        "meals",
+        "hydration",
+        "journal",
+        "screening",
+    ];
+
+    /// The `type` tag this line serializes with.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LogLine::LastSlept { .. } => "last-slept",
+            LogLine::HoursSlept { .. } => "hours-slept",
+            LogLine::SleepQuality { .. } => "sleep-quality",
+            LogLine::Medication { .. } => "medication",
+            LogLine::Hygiene { .. } => "hygiene",
+            LogLine::Alcohol { .. } => "alcohol",
+            LogLine::Mood { .. } => "mood",
+            LogLine::Exercise { .. } => "exercise",
+            LogLine::Meals { .. } => "meals",
+            LogLine::Hydration { .. } => "hydration",
+            LogLine::Journal { .. } => "journal",
+            LogLine::Screening { .. } => "screening",
+        }
+    }
+
+    pub fn recorded_at(&self) -> &str {
+        match self {
+            LogLine::LastSlept { recorded_at, .. }
+            | LogLine::HoursSlept { recorded_at, .. }
+            | LogLine::SleepQuality { recorded_at, .. }
+            | LogLine::Medication { recorded_at, .. }
+            | LogLine::Hygiene { recorded_at, .. }
+            | LogLine::Alcohol { recorded_at, .. }
+            | LogLine::Mood { recorded_at, .. }
+            | LogLine::Exercise { recorded_at, .. }
+            | LogLine::Meals { recorded_at, .. }
+            | LogLine::Hydration { recorded_at, .. }
+            | LogLine::Journal { recorded_at, .. }
+            | LogLine::Screening { recorded_at, .. } => recorded_at,
+        }
+    }
+
+    pub fn timestamp(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(self.recorded_at()).ok()
+    }
+
+    /// The local calendar day the line was recorded on.
+    pub fn day(&self) -> Option<NaiveDate> {
+        self.timestamp()
+            .map(|when| when.with_timezone(&Local).date_naive())
+    }
+
+    pub fn justification(&self) -> &str {
+        match self {
+            LogLine::LastSlept { justification, .. }
+            | LogLine::HoursSlept { justification, .. }
+            | LogLine::SleepQuality { justification, .. }
+            | LogLine::Medication { justification, .. }
+            | LogLine::Hygiene { justification, .. }
+            | LogLine::Alcohol { justification, .. }
+            | LogLine::Mood { justification, .. }
+            | LogLine::Exercise { justification, .. }
+            | LogLine::Meals { justification, .. }
+            | LogLine::Hydration { justification, .. }
+            | LogLine::Journal { justification, .. }
+            | LogLine::Screening { justification, .. } => justification,
+        }
+    }
+
+    pub fn substance(&self) -> Option<&str> {
+        match self {
+            LogLine::Medication { substance, .. } => Some(substance),
+            LogLine::Alcohol { .. } => Some("alcohol"),
+            _ => None,
+        }
+    }
+
+    /// The tracker or file a line was imported from, or None if it was recorded by notapsych.
+    pub fn imported_from(&self) -> Option<&str> {
+        match self {
+            LogLine::LastSlept { imported_from, .. }
+            | LogLine::HoursSlept { imported_from, .. }
+            | LogLine::SleepQuality { imported_from, .. } => imported_from.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// How each value in this line was extracted from what the user said, in the order asked.
+    /// Empty for lines that were not extracted by a model.
+    pub fn provenance(&self) -> &[Provenance] {
+        match self {
+            LogLine::LastSlept { provenance, .. }
+            | LogLine::HoursSlept { provenance, .. }
+            | LogLine::SleepQuality { provenance, .. }
+            | LogLine::Medication { provenance, .. }
+            | LogLine::Hygiene { provenance, .. }
+            | LogLine::Alcohol { provenance, .. }
+            | LogLine::Mood { provenance, .. }
+            | LogLine::Exercise { provenance, .. }
+            | LogLine::Meals { provenance, .. }
+            | LogLine::Hydration { provenance, .. }
+            | LogLine::Journal { provenance, .. }
+            | LogLine::Screening { provenance, .. } => provenance,
+        }
+    }
+
+    /// Whether a device measured this line rather than the user reporting it.
+    pub fn is_measured(&self) -> bool {
+        match self {
+            LogLine::LastSlept { measured, .. }
+            | LogLine::HoursSlept { measured, .. }
+            | LogLine::SleepQuality { measured, .. } => *measured,
+            _ => false,
+        }
+    }
+
+    /// The value of `metric` if this line carries it as a [Signal].
+    pub fn signal(&self, metric: &str) -> Option<f64> {
+        match self {
+            LogLine::Journal { signals, .. } => signals
+                .iter()
+                .find(|signal| signal.metric == metric)
+                .map(|signal| signal.value),
+            _ => None,
+        }
+    }
+
+    /// Rewrite legacy encodings into their current form.
+    pub fn normalized(self) -> Self {
+        match self {
+            LogLine::Medication {
+                recorded_at,
+                substance,
+                dose,
+                reported,
+                justification,
+                provenance,
+            } => LogLine::Medication {
+                recorded_at,
+                substance,
+                dose: dose.normalized(),
+                reported,
+                justification,
+                provenance,
+            },
+            log_line => log_line,
+        }
+    }
+
+    /// A short human-readable rendering of the recorded value.
+    pub fn summary(&self) -> String {
+        match self {
+            LogLine::LastSlept { awake_hours, .. } => format!("awake {awake_hours}h"),
+            LogLine::HoursSlept { sleep_hours, .. } => format!("slept {sleep_hours}h"),
+            LogLine::SleepQuality { answer, .. } => format!("quality {answer}/10"),
+            LogLine::Medication {
+                substance, dose, ..
+            } => format!("{substance} {}", dose.summary()),
+            LogLine::Hygiene { hygiene, .. } => hygiene.clone(),
+            LogLine::Alcohol {
+                standard_drinks,
+                duration_hours,
+                ..
+            } => format!("{standard_drinks} standard drinks over {duration_hours}h"),
+            LogLine::Mood {
+                valence,
+                arousal,
+                anxiety,
+                irritability,
+                emotions,
+                ..
+            } => {
+                let mut summary = format!(
+                    "valence {valence:+} energy {arousal}/10 anxiety {anxiety}/10 irritability {irritability}/10"
+                );
+                if !emotions.is_empty() {
+                    summary += &format!(" ({})", emotions.join(", "));
+                }
+                summary
+            }
+            LogLine::Exercise {
+                activity,
+                duration_minutes,
+                intensity,
+                ..
+            } => format!("{activity} {duration_minutes}min ({intensity})"),
+            LogLine::Meals {
+                meals,
+                skipped,
+                regular,
+                ..
+            } => format!(
+                "{meals} meals, {skipped} skipped, {}",
+                if *regular { "regular" } else { "irregular" }
+            ),
+            LogLine::Hydration { water_ml, .. } => format!("{water_ml}mL water"),
+            LogLine::Journal {
+                topics, sentiment, ..
+            } => format!(
+                "journal sentiment {} ({})",
+                sentiment
+                    .map(|sentiment| format!("{sentiment:+}"))
+                    .unwrap_or_else(|| "unknown".to_string()),
+                topics.join(", ")
+            ),
+            LogLine::Screening {
+                instrument,
+                total,
+                severity,
+                ..
+            } => format!("{instrument} {total} ({severity})"),
+        }
+    }
+}
+
+//////////////////////////////////////////// Provenance ////////////////////////////////////////////
+
+/// How one answer became structured data, kept so lines parsed by an old prompt or model can be
+/// found and parsed again.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct Provenance {
+    /// The slug of the question answered, or the kind of line for imports.
+    pub question: String,
+    /// What the user said, or the imported record, verbatim.
+    pub answer: String,
+    /// The model that read the answer, the parser when the backend is "local", or the tracker
+    /// exported from when it is "import".
+    pub model: String,
+    /// The service that ran the model, e.g. "ollama", "local" for the question's own parser, or
+    /// "import" for another tracker's export.
+    pub backend: String,
+    /// The `prompt_hash` of the system prompt the answer was extracted with; empty for "local".
+    pub prompt: String,
+    pub latency_ms: u64,
+    /// Whether the user looked over the extracted values before they were logged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review: Option<Review>,
+    /// The values as extracted, when the user corrected them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extracted: Option<serde_json::Value>,
+}
+
+impl Provenance {
+    pub const LOCAL: &'static str = "local";
+    pub const IMPORT: &'static str = "import";
+
+    /// `answer` to `question`, read by `parser` without a model.
+    pub fn local(question: &str, answer: String, parser: &str, latency_ms: u64) -> Self {
+        Self {
+            question: question.to_string(),
+            answer,
+            model: parser.to_string(),
+            backend: Self::LOCAL.to_string(),
+            prompt: String::new(),
+            latency_ms,
+            review: None,
+            extracted: None,
+        }
+    }
+
+    /// Note that the user looked over `extracted` and accepted it as `answer`, keeping what was
+    /// extracted if they corrected it.
+    pub fn reviewed(mut self, extracted: serde_json::Value, answer: &serde_json::Value) -> Self {
+        if *answer == extracted {
+            self.review = Some(Review::Confirmed);
+        } else {
+            self.review = Some(Review::Corrected);
+            self.extracted = Some(extracted);
+        }
+        self
+    }
+
+    /// A line of `kind` read from `record` in `source`'s export.
+    pub fn imported(kind: &str, record: &str, source: &str) -> Self {
+        Self {
+            question: kind.to_string(),
+            answer: record.to_string(),
+            model: source.to_string(),
+            backend: Self::IMPORT.to_string(),
+            prompt: String::new(),
+            latency_ms: 0,
+            review: None,
+            extracted: None,
+        }
+    }
+}
+
+/// What the user made of the values extracted from their answer.
+#[derive(
+    Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Review {
+    Confirmed,
+    Corrected,
+}
+
+////////////////////////////////////////////// Signal //////////////////////////////////////////////
+
+/// A measurement mentioned in passing in free text, e.g. "slept maybe four hours".  `metric` is
+/// one of [Signal::METRICS], on the same scale as the check-in question that measures it.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct Signal {
+    pub metric: String,
+    pub value: f64,
+    #[serde(default)]
+    pub evidence: String,
+}
+
+impl Signal {
+    pub const METRICS: &'static [&'static str] = &[
+        "sleep-hours",
+        "sleep-quality",
+        "valence",
+        "anxiety",
+        "skipped-medication",
+        "standard-drinks",
+        "exercise-minutes",
+    ];
+}
+
+///////////////////////////////////////////// Reported /////////////////////////////////////////////
+
+/// The substance and dose as the user reported them, kept when they were converted to canonical
+/// units before logging.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct Reported {
+    pub substance: String,
+    pub dose: Dose,
+}
+
+/////////////////////////////////////////////// Dose ///////////////////////////////////////////////
+
+/// How much of a substance was taken and how often.
+///
+/// Transcripts written before the non-daily variants existed encode unknown frequency as
+/// `times_daily: -1` and rarer schedules as a fraction of a day; [Dose::normalized] maps those
+/// onto the explicit variants.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(tag = "type")]
+pub enum Dose {
+    #[serde(rename = "daily")]
+    Daily {
+        quantity: f64,
+        units: String,
+        times_daily: f64,
+    },
+    #[serde(rename = "as-needed")]
+    AsNeeded {
+        quantity: f64,
+        units: String,
+        #[serde(default)]
+        max_daily: Option<f64>,
+    },
+    #[serde(rename = "weekly")]
+    Weekly {
+        quantity: f64,
+        units: String,
+        times_weekly: f64,
+    },
+    #[serde(rename = "monthly")]
+    Monthly {
+        quantity: f64,
+        units: String,
+        times_monthly: f64,
+    },
+    #[serde(rename = "once")]
+    Once { quantity: f64, units: String },
+    #[serde(rename = "taper")]
+    Taper {
+        units: String,
+        steps: Vec<TaperStep>,
+    },
+    #[serde(rename = "unknown")]
+    Unknown { quantity: f64, units: String },
+}
+
+impl Dose {
+    /// Build a dose from the flat `frequency`/`times` pair the extraction schema asks the model
+    /// for.  Unrecognized frequencies are recorded as unknown rather than guessed.
+    pub fn from_frequency(frequency: &str, quantity: f64, units: String, times: f64) -> Self {
+        match frequency {
+            "daily" => Dose::Daily {
+                quantity,
+                units,
+                times_daily: times,
+            },
+            "as-needed" => Dose::AsNeeded {
+                quantity,
+                units,
+                max_daily: if times > 0.0 { Some(times) } else { None },
+            },
+            "weekly" => Dose::Weekly {
+                quantity,
+                units,
+                times_weekly: times,
+            },
+            "monthly" => Dose::Monthly {
+                quantity,
+                units,
+                times_monthly: times,
+            },
+            "once" => Dose::Once { quantity, units },
+            "taper" => Dose::Taper {
+                units,
+                steps: vec![TaperStep {
+                    quantity,
+                    times_daily: times,
+                    days: None,
+                }],
+            },
+            _ => Dose::Unknown { quantity, units },
+        }
+    }
+
+    /// Map the legacy encodings of [Dose::Daily] onto the explicit variants.
+    pub fn normalized(self) -> Self {
+        match self {
+            Dose::Daily {
+                quantity,
+                units,
+                times_daily,
+            } if times_daily <= 0.0 => Dose::Unknown { quantity, units },
+            Dose::Daily {
+                quantity,
+                units,
+                times_daily,
+            } if times_daily * 7.0 < 0.75 => Dose::Monthly {
+                quantity,
+                units,
+                times_monthly: (times_daily * 30.0).round().max(1.0),
+            },
+            Dose::Daily {
+                quantity,
+                units,
+                times_daily,
+            } if times_daily < 1.0 => Dose::Weekly {
+                quantity,
+                units,
+                times_weekly: (times_daily * 7.0).round(),
+            },
+            dose => dose,
+        }
+    }
+
+    /// The quantity of a single dose.  A taper reports its first step.
+    pub fn quantity(&self) -> f64 {
+        match self {
+            Dose::Daily { quantity, .. }
+            | Dose::AsNeeded { quantity, .. }
+            | Dose::Weekly { quantity, .. }
+            | Dose::Monthly { quantity, .. }
+            | Dose::Once { quantity, .. }
+            | Dose::Unknown { quantity, .. } => *quantity,
+            Dose::Taper { steps, .. } => steps.first().map(|step| step.quantity).unwrap_or(0.0),
+        }
+    }
+
+    pub fn units(&self) -> &str {
+        match self {
+            Dose::Daily { units, .. }
+            | Dose::AsNeeded { units, .. }
+            | Dose::Weekly { units, .. }
+            | Dose::Monthly { units, .. }
+            | Dose::Once { units, .. }
+            | Dose::Taper { units, .. }
+            | Dose::Unknown { units, .. } => units,
+        }
+    }
+
+    /// How many doses a log entry with this dose records as taken on the day it was logged.
+    pub fn doses_taken(&self) -> f64 {
+        match self {
+            Dose::Daily { times_daily, .. } if *times_daily > 0.0 => *times_daily,
+            Dose::Taper { steps, .. } => steps
+                .first()
+                .map(|step| step.times_daily)
+                .filter(|times| *times > 0.0)
+                .unwrap_or(1.0),
+            _ => 1.0,
+        }
+    }
+
+    /// The total quantity taken on the day the entry was logged.
+    pub fn total(&self) -> f64 {
+        self.quantity() * self.doses_taken()
+    }
+
+    pub fn summary(&self) -> String {
+        match self {
+            Dose::Daily {
+                quantity,
+                units,
+                times_daily,
+            } => format!("{} {times_daily}x daily", amount(*quantity, units)),
+            Dose::AsNeeded {
+                quantity,
+                units,
+                max_daily: Some(max_daily),
+            } => format!(
+                "{} as needed, up to {max_daily}x daily",
+                amount(*quantity, units)
+            ),
+            Dose::AsNeeded {
+                quantity,
+                units,
+                max_daily: None,
+            } => format!("{} as needed", amount(*quantity, units)),
+            Dose::Weekly {
+                quantity,
+                units,
+                times_weekly,
+            } => format!("{} {times_weekly}x weekly", amount(*quantity, units)),
+            Dose::Monthly {
+                quantity,
+                units,
+                times_monthly,
+            } => format!("{} {times_monthly}x monthly", amount(*quantity, units)),
+            Dose::Once { quantity, units } => format!("{} once", amount(*quantity, units)),
+            Dose::Taper { units, steps } => {
+                let steps = steps
+                    .iter()
+                    .map(|step| match step.days {
+                        Some(days) => format!(
+                            "{} {}x daily for {days}d",
+                            amount(step.quantity, units),
+                            step.times_daily
+                        ),
+                        None => format!(
+                            "{} {}x daily",
+                            amount(step.quantity, units),
+                            step.times_daily
+                        ),
+                    })
+                    .collect::<Vec<_>>();
+                format!("taper {}", steps.join(", then "))
+            }
+            Dose::Unknown { quantity, units } => {
+                format!("{}, frequency unknown", amount(*quantity, units))
+            }
+        }
+    }
+}
+
+/// Render a quantity with its units:  "30mg" but "2 standard drinks".
+fn amount(quantity: f64, units: &str) -> String {
+    if units.len() <= 3 && units.chars().all(char::is_alphabetic) {
+        format!("{quantity}{units}")
+    } else {
+        format!("{quantity} {units}")
+    }
+}
+
+///////////////////////////////////////////// TaperStep ////////////////////////////////////////////
+
+/// One step of a taper:  `quantity` taken `times_daily` for `days` days (until further notice
+/// when None).
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct TaperStep {
+    pub quantity: f64,
+    pub times_daily: f64,
+    #[serde(default)]
+    pub days: Option<u32>,
+}
+
+/////////////////////////////////////////////// Entry //////////////////////////////////////////////
+
+/// One line of a transcript.  Lines this version cannot interpret are surfaced rather than
+/// aborting the read, so old readers keep working on newer transcripts.
+#[derive(Clone, Debug)]
+pub enum Entry {
+    LogLine(LogLine),
+    Unknown { line: usize, kind: String },
+    Malformed { line: usize, error: String },
+}
+
+impl Entry {
+    fn parse(line: usize, text: &str) -> Self {
+        match serde_json::from_str::<LogLine>(text) {
+            Ok(log_line) => Entry::LogLine(log_line.normalized()),
+            Err(err) => match serde_json::from_str::<serde_json::Value>(text) {
+                Ok(value) => match value.get("type").and_then(|kind| kind.as_str()) {
+                    Some(kind) if !LogLine::KINDS.contains(&kind) => Entry::Unknown {
+                        line,
+                        kind: kind.to_string(),
+                    },
+                    _ => Entry::Malformed {
+                        line,
+                        error: err.to_string(),
+                    },
+                },
+                Err(_) => Entry::Malformed {
+                    line,
+                    error: err.to_string(),
+                },
+            },
+        }
+    }
+}
+
+///////////////////////////////////////////// Transcript ///////////////////////////////////////////
+
+/// Streams the entries of a JSONL transcript.
+pub struct Transcript<R: BufRead> {
+    lines: std::io::Lines<R>,
+    line: usize,
+}
+
+impl Transcript<BufReader<File>> {
+    pub fn open(path: &str) -> Result<Self, std::io::Error> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+
+    /// Open the transcript named by `NOTAPSYCH_TRANSCRIPT`.
+    pub fn from_env() -> Result<Self, std::io::Error> {
+        match std::env::var("NOTAPSYCH_TRANSCRIPT") {
+            Ok(transcript) => Self::open(&transcript),
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "NOTAPSYCH_TRANSCRIPT is not set",
+            )),
+        }
+    }
+}
+
+impl<R: BufRead> Transcript<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            line: 0,
+        }
+    }
+
+    /// Only the lines that parsed, in order.
+    pub fn log_lines(self) -> impl Iterator<Item = LogLine> {
+        self.filter_map(|entry| match entry {
+            Entry::LogLine(log_line) => Some(log_line),
+            _ => None,
+        })
+    }
+
+    /// Only the lines that parsed and pass `filter`, in order.
+    pub fn query<'a>(self, filter: &'a Filter) -> impl Iterator<Item = LogLine> + 'a
+    where
+        R: 'a,
+    {
+        self.log_lines()
+            .filter(move |log_line| filter.matches(log_line))
+    }
+}
+
+impl<R: BufRead> Iterator for Transcript<R> {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        loop {
+            self.line += 1;
+            let text = match self.lines.next()? {
+                Ok(text) => text,
+                Err(err) => {
+                    return Some(Entry::Malformed {
+                        line: self.line,
+                        error: err.to_string(),
+                    });
+                }
+            };
+            if text.trim().is_empty() {
+                continue;
+            }
+            return Some(Entry::parse(self.line, &text));
+        }
+    }
+}
+
+/// A log line as written to a transcript, stamped with the [SCHEMA_VERSION] it was written at.
+/// Readers ignore the stamp; it is for other tools and for validation.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Versioned<'a> {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub log_line: &'a LogLine,
+}
+
+impl<'a> Versioned<'a> {
+    /// Stamp `log_line` with the current [SCHEMA_VERSION].
+    pub fn new(log_line: &'a LogLine) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            log_line,
+        }
+    }
+}
+
+/// Append `log_line` to the transcript at `path`, creating it if need be.
+pub fn append(path: &str, log_line: &LogLine) -> Result<(), std::io::Error> {
+    let mut log = OpenOptions::new().append(true).create(true).open(path)?;
+    let line = serde_json::to_string(&Versioned::new(log_line))
+        .expect("log line should always serialize")
+        + "\n";
+    log.write_all(line.as_bytes())
+}
+
+/////////////////////////////////////////////// Filter /////////////////////////////////////////////
+
+/// Select log lines by kind, local day, substance, and prompt.  Empty criteria match everything.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    pub kinds: Vec<String>,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    pub substance: Option<String>,
+    /// Keep lines extracted with this version of a prompt, by `prompt_hash`.
+    pub prompt: Option<String>,
+}
+
+impl Filter {
+    pub fn matches(&self, log_line: &LogLine) -> bool {
+        if !self.kinds.is_empty() && !self.kinds.iter().any(|kind| kind == log_line.kind()) {
+            return false;
+        }
+        if self.since.is_some() || self.until.is_some() {
+            let Some(day) = log_line.day() else {
+                return false;
+            };
+            if self.since.map(|since| day < since).unwrap_or(false)
+                || self.until.map(|until| day > until).unwrap_or(false)
+            {
+                return false;
+            }
+        }
+        if let Some(substance) = self.substance.as_ref() {
+            match log_line.substance() {
+                Some(s) if s.eq_ignore_ascii_case(substance) => {}
+                _ => return false,
+            }
+        }
+        if let Some(prompt) = self.prompt.as_ref() {
+            if !log_line.provenance().iter().any(|p| &p.prompt == prompt) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provenance_round_trips() {
+        let mut provenance = Provenance::local("last-slept", "7:30".to_string(), "hours-since", 3);
+        provenance.review = Some(Review::Corrected);
+        provenance.extracted = Some(serde_json::json!({"awake_hours": 8}));
+        let log_line = LogLine::LastSlept {
+            recorded_at: "2025-06-10T15:15:00-07:00".to_string(),
+            awake_hours: 7.75,
+            justification: String::new(),
+            imported_from: None,
+            measured: false,
+            provenance: vec![provenance],
+        };
+        let json = serde_json::to_string(&log_line).unwrap();
+        let parsed: LogLine = serde_json::from_str(&json).unwrap();
+        let [provenance] = parsed.provenance() else {
+            panic!("expected one provenance in {json}");
+        };
+        assert_eq!("hours-since", provenance.model);
+        assert_eq!(Provenance::LOCAL, provenance.backend);
+        assert_eq!(Some(Review::Corrected), provenance.review);
+        assert_eq!(
+            Some(serde_json::json!({"awake_hours": 8})),
+            provenance.extracted
+        );
+    }
+
+    #[test]
+    fn reviewed_keeps_what_was_corrected() {
+        let provenance = || Provenance::local("slept-how-long", "7".to_string(), "duration", 0);
+        let extracted = serde_json::json!({"sleep_hours": 7, "justification": ""});
+        let confirmed = provenance().reviewed(extracted.clone(), &extracted);
+        assert_eq!(Some(Review::Confirmed), confirmed.review);
+        assert_eq!(None, confirmed.extracted);
+        let corrected = serde_json::json!({"sleep_hours": 6, "justification": ""});
+        let corrected = provenance().reviewed(extracted.clone(), &corrected);
+        assert_eq!(Some(Review::Corrected), corrected.review);
+        assert_eq!(Some(extracted), corrected.extracted);
+    }
+
+    #[test]
+    fn lines_without_provenance_still_parse() {
+        let json = r#"{"type": "hygiene", "recorded_at": "2025-06-10T08:00:00-07:00",
+            "hygiene": "GOOD", "justification": ""}"#;
+        let parsed: LogLine = serde_json::from_str(json).unwrap();
+        assert!(parsed.provenance().is_empty());
+    }
+
+    #[test]
+    fn alcohol_keeps_what_was_reported() {
+        let json = r#"{"type": "alcohol", "recorded_at": "2025-06-10T02:00:00-07:00",
+            "standard_drinks": 2.0, "started_at": "2025-06-09T22:00:00-07:00",
+            "duration_hours": 4.0, "justification": "",
+            "reported": {"substance": "beer", "dose": {"type": "once", "quantity": 2.0,
+                "units": "pints"}}}"#;
+        let parsed: LogLine = serde_json::from_str(json).unwrap();
+        let LogLine::Alcohol { reported, .. } = &parsed else {
+            panic!("expected an alcohol line");
+        };
+        assert_eq!("beer", reported.as_ref().unwrap().substance);
+        let round_trip: serde_json::Value = serde_json::to_value(&parsed).unwrap();
+        assert_eq!("pints", round_trip["reported"]["dose"]["units"]);
+    }
+
+    #[test]
+    fn unknown_sentiment_is_not_neutral() {
+        let json = r#"{"type": "journal", "recorded_at": "2025-06-10T12:00:00-07:00",
+            "text": "..."}"#;
+        let journal: LogLine = serde_json::from_str(json).unwrap();
+        assert!(journal.summary().contains("unknown"));
+        let LogLine::Journal { sentiment, .. } = journal else {
+            panic!("expected a journal line");
+        };
+        assert_eq!(None, sentiment);
+    }
+
+    const TRANSCRIPT: &str = r#"{"schema_version": 1, "type": "hygiene", "recorded_at": "2025-06-08T12:00:00-07:00", "hygiene": "GOOD", "justification": ""}
+
+{"type": "telepathy", "recorded_at": "2025-06-09T12:00:00-07:00"}
+not json
+{"type": "hygiene", "recorded_at": "2025-06-09T12:00:00-07:00"}
+{"type": "last-slept", "recorded_at": "2025-06-10T12:00:00-07:00", "awake_hours": 4.0, "justification": ""}
+{"type": "hygiene", "recorded_at": "2025-06-12T12:00:00-07:00", "hygiene": "FAIR", "justification": ""}
+"#;
+
+    #[test]
+    fn transcript_surfaces_unknown_and_malformed_lines() {
+        let entries = Transcript::new(TRANSCRIPT.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(6, entries.len());
+        assert!(matches!(
+            &entries[0],
+            Entry::LogLine(LogLine::Hygiene { .. })
+        ));
+        let Entry::Unknown { line, kind } = &entries[1] else {
+            panic!("expected an unknown line, got {:?}", entries[1]);
+        };
+        assert_eq!((3, "telepathy"), (*line, kind.as_str()));
+        assert!(matches!(&entries[2], Entry::Malformed { line: 4, .. }));
+        let Entry::Malformed { line, error } = &entries[3] else {
+            panic!("expected a malformed line, got {:?}", entries[3]);
+        };
+        assert_eq!(5, *line);
+        assert!(error.contains("hygiene"), "{error}");
+        assert!(matches!(
+            &entries[4],
+            Entry::LogLine(LogLine::LastSlept { .. })
+        ));
+    }
+
+    #[test]
+    fn filter_by_kind() {
+        let filter = Filter {
+            kinds: vec!["hygiene".to_string()],
+            ..Filter::default()
+        };
+        let kept = Transcript::new(TRANSCRIPT.as_bytes())
+            .query(&filter)
+            .map(|log_line| log_line.summary())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["GOOD", "FAIR"], kept);
+    }
+
+    #[test]
+    fn filter_since_and_until_are_inclusive() {
+        let log_lines = Transcript::new(TRANSCRIPT.as_bytes())
+            .log_lines()
+            .collect::<Vec<_>>();
+        let days = log_lines
+            .iter()
+            .map(|log_line| log_line.day().unwrap())
+            .collect::<Vec<_>>();
+        let kept = |filter: Filter| {
+            log_lines
+                .iter()
+                .filter(|log_line| filter.matches(log_line))
+                .map(LogLine::kind)
+                .collect::<Vec<_>>()
+        };
+        let since = Filter {
+            since: Some(days[1]),
+            ..Filter::default()
+        };
+        assert_eq!(vec!["last-slept", "hygiene"], kept(since));
+        let until = Filter {
+            until: Some(days[1]),
+            ..Filter::default()
+        };
+        assert_eq!(vec!["hygiene", "last-slept"], kept(until));
+        let both = Filter {
+            since: Some(days[1]),
+            until: Some(days[1]),
+            ..Filter::default()
+        };
+        assert_eq!(vec!["last-slept"], kept(both));
+    }
+
+    #[test]
+    fn filter_since_drops_unparseable_times() {
+        let log_line: LogLine = serde_json::from_str(
+            r#"{"type": "hygiene", "recorded_at": "yesterday", "hygiene": "GOOD",
+                "justification": ""}"#,
+        )
+        .unwrap();
+        assert!(Filter::default().matches(&log_line));
+        let filter = Filter {
+            since: NaiveDate::from_ymd_opt(2025, 1, 1),
+            ..Filter::default()
+        };
+        assert!(!filter.matches(&log_line));
+    }
+
+    #[test]
+    fn versioned_lines_carry_the_schema_version() {
+        let log_line = Transcript::new(TRANSCRIPT.as_bytes())
+            .log_lines()
+            .next()
+            .unwrap();
+        let json = serde_json::to_value(Versioned::new(&log_line)).unwrap();
+        assert_eq!(SCHEMA_VERSION, json["schema_version"]);
+        assert_eq!("hygiene", json["type"]);
+    }
+
+    #[test]
+    fn from_frequency_variants() {
+        let mg = || "mg".to_string();
+        assert_eq!(
+            Dose::Daily {
+                quantity: 30.0,
+                units: mg(),
+                times_daily: 3.0
+            },
+            Dose::from_frequency("daily", 30.0, mg(), 3.0)
+        );
+        assert_eq!(
+            Dose::AsNeeded {
+                quantity: 200.0,
+                units: mg(),
+                max_daily: None
+            },
+            Dose::from_frequency("as-needed", 200.0, mg(), 0.0)
+        );
+        assert_eq!(
+            Dose::AsNeeded {
+                quantity: 200.0,
+                units: mg(),
+                max_daily: Some(4.0)
+            },
+            Dose::from_frequency("as-needed", 200.0, mg(), 4.0)
+        );
+        assert_eq!(
+            Dose::Weekly {
+                quantity: 2.5,
+                units: mg(),
+                times_weekly: 1.0
+            },
+            Dose::from_frequency("weekly", 2.5, mg(), 1.0)
+        );
+        assert_eq!(
+            Dose::Monthly {
+                quantity: 1.0,
+                units: mg(),
+                times_monthly: 2.0
+            },
+            Dose::from_frequency("monthly", 1.0, mg(), 2.0)
+        );
+        assert_eq!(
+            Dose::Once {
+                quantity: 5.0,
+                units: mg()
+            },
+            Dose::from_frequency("once", 5.0, mg(), 1.0)
+        );
+        assert_eq!(
+            Dose::Taper {
+                units: mg(),
+                steps: vec![TaperStep {
+                    quantity: 10.0,
+                    times_daily: 2.0,
+                    days: None
+                }]
+            },
+            Dose::from_frequency("taper", 10.0, mg(), 2.0)
+        );
+        assert_eq!(
+            Dose::Unknown {
+                quantity: 5.0,
+                units: mg()
+            },
+            Dose::from_frequency("fortnightly", 5.0, mg(), 1.0)
+        );
+    }
+
+    #[test]
+    fn normalized_legacy_daily() {
+        let daily = |times_daily| Dose::Daily {
+            quantity: 10.0,
+            units: "mg".to_string(),
+            times_daily,
+        };
+        assert_eq!(
+            Dose::Unknown {
+                quantity: 10.0,
+                units: "mg".to_string()
+            },
+            daily(-1.0).normalized()
+        );
+        assert_eq!(
+            Dose::Weekly {
+                quantity: 10.0,
+                units: "mg".to_string(),
+                times_weekly: 1.0
+            },
+            daily(1.0 / 7.0).normalized()
+        );
+        assert_eq!(
+            Dose::Monthly {
+                quantity: 10.0,
+                units: "mg".to_string(),
+                times_monthly: 1.0
+            },
+            daily(1.0 / 30.0).normalized()
+        );
+        assert_eq!(daily(2.0), daily(2.0).normalized());
+    }
+
+    #[test]
+    fn normalized_leaves_explicit_variants() {
+        let once = Dose::Once {
+            quantity: 1.0,
+            units: "mg".to_string(),
+        };
+        assert_eq!(once.clone(), once.normalized());
+    }
+
+    #[test]
+    fn total_counts_doses_taken() {
+        assert_eq!(
+            60.0,
+            Dose::from_frequency("daily", 30.0, "mg".into(), 2.0).total()
+        );
+        assert_eq!(
+            30.0,
+            Dose::from_frequency("weekly", 30.0, "mg".into(), 1.0).total()
+        );
+        assert_eq!(
+            30.0,
+            Dose::from_frequency("unknown", 30.0, "mg".into(), 0.0).total()
+        );
+    }
+}
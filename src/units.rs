@@ -0,0 +1,396 @@
+use crate::transcript::{Dose, TaperStep};
+
+/// Caffeine in one 8oz cup of brewed coffee.
+pub const CAFFEINE_MG_PER_CUP_OF_COFFEE: f64 = 95.0;
+/// Caffeine in one shot of espresso.
+pub const CAFFEINE_MG_PER_ESPRESSO: f64 = 63.0;
+/// Caffeine in one 8oz cup of black tea.
+pub const CAFFEINE_MG_PER_CUP_OF_TEA: f64 = 47.0;
+/// Caffeine in one 8.4oz energy drink.
+pub const CAFFEINE_MG_PER_ENERGY_DRINK: f64 = 80.0;
+/// Caffeine in one 12oz can of cola.
+pub const CAFFEINE_MG_PER_SODA: f64 = 34.0;
+/// Nicotine content of one cigarette.  This matches what check-ins have always recorded.
+pub const NICOTINE_MG_PER_CIGARETTE: f64 = 10.0;
+/// Cigarettes in a pack.
+pub const CIGARETTES_PER_PACK: f64 = 20.0;
+
+/// Water in one 8oz cup or glass.
+pub const ML_PER_CUP: f64 = 240.0;
+/// Water in one disposable bottle.
+pub const ML_PER_BOTTLE_OF_WATER: f64 = 500.0;
+/// One US fluid ounce.
+pub const ML_PER_FLUID_OUNCE: f64 = 29.57;
+
+/// Ethanol in one US standard drink, in grams.
+pub const GRAMS_ETHANOL_PER_STANDARD_DRINK: f64 = 14.0;
+
+/// Canonical units.
+pub const MG: &str = "mg";
+pub const ML: &str = "mL";
+pub const IU: &str = "IU";
+pub const STANDARD_DRINKS: &str = "standard drinks";
+
+///////////////////////////////////////////// Conversion ///////////////////////////////////////////
+
+/// Multiply a quantity in the reported units by `factor` to get `units`, and record it under
+/// `substance`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conversion {
+    pub substance: String,
+    pub units: &'static str,
+    pub factor: f64,
+}
+
+/// Lowercase, trim, and drop a trailing plural so "Cups" and "cup" compare equal.
+fn canonical(word: &str) -> String {
+    let word = word.trim().to_lowercase();
+    let word = word.trim_end_matches('.');
+    if let Some(stem) = word.strip_suffix("es") {
+        if stem.ends_with("sh")
+            || stem.ends_with("ch")
+            || stem.ends_with("ss")
+            || stem.ends_with('x')
+        {
+            return stem.to_string();
+        }
+    }
+    match word.strip_suffix('s') {
+        Some(stem) if !stem.ends_with('s') && !stem.is_empty() => stem.to_string(),
+        _ => word.to_string(),
+    }
+}
+
+/// The canonical name for a substance the user might call something else.  Substances without
+/// an alias are returned as given.
+pub fn canonical_substance(substance: &str) -> String {
+    let lower = substance.trim().to_lowercase();
+    match lower.as_str() {
+        "caffeine" | "nicotine" | "alcohol" | "water" => lower,
+        "coffee" | "espresso" | "latte" | "cappuccino" | "americano" | "cold brew" | "tea"
+        | "black tea" | "green tea" | "energy drink" | "red bull" | "monster" | "soda" | "cola"
+        | "coke" | "diet coke" | "pepsi" => "caffeine".to_string(),
+        "cigarette" | "cigarettes" | "tobacco" | "smoke" | "smokes" | "vape" => {
+            "nicotine".to_string()
+        }
+        "beer" | "ipa" | "lager" | "wine" | "red wine" | "white wine" | "whisky" | "whiskey"
+        | "bourbon" | "vodka" | "gin" | "rum" | "tequila" | "liquor" | "booze" | "ethanol"
+        | "shots" | "cocktail" => "alcohol".to_string(),
+        "sparkling water" | "seltzer" | "h2o" => "water".to_string(),
+        _ => substance.trim().to_string(),
+    }
+}
+
+/// Look up how to convert `units` of `substance` into canonical units, or None if the units are
+/// not recognized.
+pub fn conversion(substance: &str, units: &str) -> Option<Conversion> {
+    let raw = substance.trim().to_lowercase();
+    let substance = canonical_substance(substance);
+    let units = canonical(units);
+    let convert = |substance: &str, units: &'static str, factor: f64| {
+        Some(Conversion {
+            substance: substance.to_string(),
+            units,
+            factor,
+        })
+    };
+    // Alcohol is counted in standard drinks whatever it was measured in, mL and grams included.
+    if substance == "alcohol" {
+        return standard_drinks(&raw, &units)
+            .and_then(|factor| convert("alcohol", STANDARD_DRINKS, factor));
+    }
+    // Units that mean the same thing for any substance.
+    match units.as_str() {
+        "mg" | "milligram" => return convert(&substance, MG, 1.0),
+        "mcg" | "µg" | "ug" | "microgram" => return convert(&substance, MG, 0.001),
+        "g" | "gram" => return convert(&substance, MG, 1_000.0),
+        "ml" | "milliliter" | "millilitre" | "cc" => return convert(&substance, ML, 1.0),
+        "l" | "liter" | "litre" => return convert(&substance, ML, 1_000.0),
+        "iu" | "international unit" => return convert(&substance, IU, 1.0),
+        _ => {}
+    }
+    match (substance.as_str(), units.as_str()) {
+        ("caffeine", "espresso" | "shot" | "shot of espresso") => {
+            convert("caffeine", MG, CAFFEINE_MG_PER_ESPRESSO)
+        }
+        ("caffeine", "cup" | "mug" | "cup of coffee" | "cup of tea" | "glass") => {
+            if raw.contains("tea") || units.contains("tea") {
+                convert("caffeine", MG, CAFFEINE_MG_PER_CUP_OF_TEA)
+            } else if raw.contains("espresso") {
+                convert("caffeine", MG, CAFFEINE_MG_PER_ESPRESSO)
+            } else {
+                convert("caffeine", MG, CAFFEINE_MG_PER_CUP_OF_COFFEE)
+            }
+        }
+        ("caffeine", "can" | "bottle" | "energy drink" | "red bull" | "monster") => {
+            if raw.contains("soda") || raw.contains("cola") || raw.contains("coke") {
+                convert("caffeine", MG, CAFFEINE_MG_PER_SODA)
+            } else if raw.contains("monster") {
+                convert("caffeine", MG, 2.0 * CAFFEINE_MG_PER_ENERGY_DRINK)
+            } else {
+                convert("caffeine", MG, CAFFEINE_MG_PER_ENERGY_DRINK)
+            }
+        }
+        ("nicotine", "cigarette" | "cig" | "smoke") => {
+            convert("nicotine", MG, NICOTINE_MG_PER_CIGARETTE)
+        }
+        ("nicotine", "pack") => convert(
+            "nicotine",
+            MG,
+            NICOTINE_MG_PER_CIGARETTE * CIGARETTES_PER_PACK,
+        ),
+        ("water", "cup" | "glass" | "mug") => convert("water", ML, ML_PER_CUP),
+        ("water", "bottle") => convert("water", ML, ML_PER_BOTTLE_OF_WATER),
+        ("water", "oz" | "ounce" | "fl oz") => convert("water", ML, ML_PER_FLUID_OUNCE),
+        // Units nobody uses for anything but alcohol, whatever the brand is called.
+        (
+            _,
+            "standard drink" | "beer" | "cocktail" | "fifth" | "handle" | "six pack" | "six-pack"
+            | "twelve pack" | "twelve-pack" | "thirty rack" | "thirty-rack",
+        ) => standard_drinks(&raw, &units)
+            .and_then(|factor| convert("alcohol", STANDARD_DRINKS, factor)),
+        _ => None,
+    }
+}
+
+/// Standard drinks per reported unit of alcohol.
+fn standard_drinks(raw: &str, units: &str) -> Option<f64> {
+    let factor = match units {
+        "standard drink" | "drink" | "beer" | "can" | "bottle" | "glass" | "shot" | "cocktail" => {
+            if units == "bottle" && raw.contains("wine") {
+                5.0
+            } else {
+                1.0
+            }
+        }
+        "pint" => 1.33,
+        "double" => 2.0,
+        "six pack" | "six-pack" => 6.0,
+        "twelve pack" | "twelve-pack" => 12.0,
+        "thirty rack" | "thirty-rack" => 30.0,
+        "fifth" => 17.0,
+        "handle" => 39.0,
+        "oz" | "ounce" | "fl oz" => 1.0 / ounces_per_standard_drink(raw),
+        "ml" | "milliliter" | "millilitre" | "cc" => {
+            1.0 / (ounces_per_standard_drink(raw) * ML_PER_FLUID_OUNCE)
+        }
+        "l" | "liter" | "litre" => 1_000.0 / (ounces_per_standard_drink(raw) * ML_PER_FLUID_OUNCE),
+        "g" | "gram" => 1.0 / GRAMS_ETHANOL_PER_STANDARD_DRINK,
+        "mg" | "milligram" => 0.001 / GRAMS_ETHANOL_PER_STANDARD_DRINK,
+        _ => return None,
+    };
+    Some(factor)
+}
+
+/// Fluid ounces of a drink that hold one standard drink:  5oz of wine, 12oz of beer, or 1.5oz of
+/// anything else, taken to be spirits.
+fn ounces_per_standard_drink(raw: &str) -> f64 {
+    if raw.contains("wine") {
+        5.0
+    } else if raw.contains("beer") || raw.contains("ipa") || raw.contains("lager") {
+        12.0
+    } else {
+        1.5
+    }
+}
+
+/// Convert `dose` of `substance` into canonical units.  The model is good at pulling "two cups of
+/// coffee" out of free text and bad at knowing how much caffeine that is, so the conversion is a
+/// fixed table and the same answer always normalizes the same way.
+///
+/// Returns the canonical substance name and the converted dose, or None when the units are not
+/// recognized or already canonical and the dose should be logged as reported.
+pub fn normalize(substance: &str, dose: &Dose) -> Option<(String, Dose)> {
+    let conversion = conversion(substance, dose.units())?;
+    if conversion.substance == substance
+        && conversion.units == dose.units()
+        && conversion.factor == 1.0
+    {
+        return None;
+    }
+    let units = conversion.units.to_string();
+    let scale = |quantity: &f64| quantity * conversion.factor;
+    let dose = match dose {
+        Dose::Daily {
+            quantity,
+            times_daily,
+            ..
+        } => Dose::Daily {
+            quantity: scale(quantity),
+            units,
+            times_daily: *times_daily,
+        },
+        Dose::AsNeeded {
+            quantity,
+            max_daily,
+            ..
+        } => Dose::AsNeeded {
+            quantity: scale(quantity),
+            units,
+            max_daily: *max_daily,
+        },
+        Dose::Weekly {
+            quantity,
+            times_weekly,
+            ..
+        } => Dose::Weekly {
+            quantity: scale(quantity),
+            units,
+            times_weekly: *times_weekly,
+        },
+        Dose::Monthly {
+            quantity,
+            times_monthly,
+            ..
+        } => Dose::Monthly {
+            quantity: scale(quantity),
+            units,
+            times_monthly: *times_monthly,
+        },
+        Dose::Once { quantity, .. } => Dose::Once {
+            quantity: scale(quantity),
+            units,
+        },
+        Dose::Taper { steps, .. } => Dose::Taper {
+            units,
+            steps: steps
+                .iter()
+                .map(|step| TaperStep {
+                    quantity: scale(&step.quantity),
+                    times_daily: step.times_daily,
+                    days: step.days,
+                })
+                .collect(),
+        },
+        Dose::Unknown { quantity, .. } => Dose::Unknown {
+            quantity: scale(quantity),
+            units,
+        },
+    };
+    Some((conversion.substance, dose))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn converts(substance: &str, units: &str) -> Option<(String, &'static str, f64)> {
+        conversion(substance, units).map(|c| (c.substance, c.units, c.factor))
+    }
+
+    #[test]
+    fn conversion_caffeine() {
+        assert_eq!(
+            Some(("caffeine".to_string(), MG, CAFFEINE_MG_PER_CUP_OF_COFFEE)),
+            converts("coffee", "cups")
+        );
+        assert_eq!(
+            Some(("caffeine".to_string(), MG, CAFFEINE_MG_PER_CUP_OF_TEA)),
+            converts("green tea", "cup")
+        );
+        assert_eq!(
+            Some(("caffeine".to_string(), MG, CAFFEINE_MG_PER_ESPRESSO)),
+            converts("espresso", "shots")
+        );
+        assert_eq!(
+            Some(("caffeine".to_string(), MG, CAFFEINE_MG_PER_SODA)),
+            converts("diet coke", "can")
+        );
+    }
+
+    #[test]
+    fn conversion_nicotine() {
+        assert_eq!(
+            Some(("nicotine".to_string(), MG, NICOTINE_MG_PER_CIGARETTE)),
+            converts("cigarettes", "cigarette")
+        );
+        assert_eq!(
+            Some((
+                "nicotine".to_string(),
+                MG,
+                NICOTINE_MG_PER_CIGARETTE * CIGARETTES_PER_PACK
+            )),
+            converts("cigarettes", "packs")
+        );
+    }
+
+    #[test]
+    fn conversion_water() {
+        assert_eq!(
+            Some(("water".to_string(), ML, ML_PER_CUP)),
+            converts("water", "glasses")
+        );
+        assert_eq!(
+            Some(("water".to_string(), ML, ML_PER_FLUID_OUNCE)),
+            converts("water", "oz")
+        );
+        assert_eq!(
+            Some(("water".to_string(), ML, 1_000.0)),
+            converts("water", "L")
+        );
+    }
+
+    #[test]
+    fn conversion_alcohol_servings() {
+        let drinks = |factor| Some(("alcohol".to_string(), STANDARD_DRINKS, factor));
+        assert_eq!(drinks(1.0), converts("beer", "beers"));
+        assert_eq!(drinks(1.0), converts("red wine", "glass"));
+        assert_eq!(drinks(5.0), converts("wine", "bottle"));
+        assert_eq!(drinks(1.0), converts("whiskey", "shots"));
+        assert_eq!(drinks(1.0), converts("margarita", "cocktail"));
+        assert_eq!(drinks(1.0), converts("cold ones", "beer"));
+        assert_eq!(drinks(17.0), converts("Jack", "fifth"));
+        assert_eq!(drinks(30.0), converts("bud", "thirty rack"));
+    }
+
+    #[test]
+    fn conversion_alcohol_by_volume_and_mass() {
+        let (substance, units, factor) = converts("wine", "mL").unwrap();
+        assert_eq!(("alcohol", STANDARD_DRINKS), (substance.as_str(), units));
+        assert!((750.0 * factor - 5.07).abs() < 0.01, "{}", 750.0 * factor);
+        let (_, _, factor) = converts("beer", "L").unwrap();
+        assert!((factor - 2.82).abs() < 0.01, "{factor}");
+        let (substance, units, factor) = converts("alcohol", "g").unwrap();
+        assert_eq!(("alcohol", STANDARD_DRINKS), (substance.as_str(), units));
+        assert_eq!(1.0, 14.0 * factor);
+        let (_, _, factor) = converts("vodka", "oz").unwrap();
+        assert_eq!(1.0, 1.5 * factor);
+    }
+
+    #[test]
+    fn conversion_generic_units() {
+        assert_eq!(
+            Some(("fluoxetine".to_string(), MG, 1.0)),
+            converts("fluoxetine", "mg")
+        );
+        assert_eq!(
+            Some(("vitamin D".to_string(), MG, 0.001)),
+            converts("vitamin D", "mcg")
+        );
+        assert_eq!(None, converts("fluoxetine", "pill"));
+    }
+
+    #[test]
+    fn normalize_keeps_what_was_reported() {
+        let dose = Dose::Once {
+            quantity: 2.0,
+            units: "cup".to_string(),
+        };
+        assert_eq!(
+            Some((
+                "caffeine".to_string(),
+                Dose::Once {
+                    quantity: 2.0 * CAFFEINE_MG_PER_CUP_OF_COFFEE,
+                    units: MG.to_string()
+                }
+            )),
+            normalize("coffee", &dose)
+        );
+        let dose = Dose::Daily {
+            quantity: 20.0,
+            units: MG.to_string(),
+            times_daily: 1.0,
+        };
+        assert_eq!(None, normalize("fluoxetine", &dose));
+    }
+}